@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rssli::{parser::parse_tokens, tokenizer::tokenize, Runtime};
+
+/**
+ * Generates a large program: many function definitions followed by calls,
+ * mixing strings, comments and nested expressions.
+ */
+fn generate_large_program(functions: usize) -> String {
+    let mut prog = String::from("(\n");
+    for i in 0..functions {
+        prog.push_str(&format!(
+            "    ; function number {i}\n    (fn func-{i} (a b) (if (< a b) (+ a (* b {i})) (str.format \"value %v\" (- a b))))\n"
+        ));
+    }
+    for i in 0..functions {
+        prog.push_str(&format!("    (def result-{i} (func-{i} {i} 0x{:x}))\n", i * 3));
+    }
+    prog.push_str(")\n");
+    prog
+}
+
+/// Writes `depth` files where each one imports the next and defines a function
+fn generate_import_chain(dir: &Path, depth: usize) -> PathBuf {
+    fs::create_dir_all(dir).expect("Unable to create import chain directory");
+    for i in 0..depth {
+        let import = if i + 1 < depth {
+            format!("(import \"chain-{}\")", i + 1)
+        } else {
+            String::new()
+        };
+        let content = format!("(\n    {import}\n    (fn chain-fn-{i} (x) (+ x {i}))\n    (def chain-var-{i} (chain-fn-{i} 1))\n)\n");
+        fs::write(dir.join(format!("chain-{i}.lsp")), content)
+            .expect("Unable to write import chain file");
+    }
+    dir.join("chain-0.lsp")
+}
+
+fn bench_tokenize_parse(c: &mut Criterion) {
+    let prog = generate_large_program(500);
+
+    c.bench_function("tokenize large program", |b| {
+        b.iter(|| tokenize(black_box(&prog)).unwrap())
+    });
+
+    let tokens = tokenize(&prog).unwrap();
+    c.bench_function("parse large program", |b| {
+        b.iter(|| parse_tokens(black_box(&tokens)).unwrap())
+    });
+
+    c.bench_function("tokenize and parse large program", |b| {
+        b.iter(|| parse_tokens(&tokenize(black_box(&prog)).unwrap()).unwrap())
+    });
+}
+
+fn bench_fib(c: &mut Criterion) {
+    let prog = r#"
+    (
+        (fn fib (x) (if (< x 3) 1 (+ (fib (- x 1)) (fib (- x 2)))))
+        (fib 15)
+    )
+    "#;
+
+    c.bench_function("recursive fib 15", |b| {
+        b.iter_batched(
+            Runtime::new_default,
+            |mut runtime| runtime.eval_string(black_box(prog)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_closure_pipeline(c: &mut Criterion) {
+    let prog = r#"
+    (
+        (fn make-adder (n) (fn (x) (+ x n)))
+        (def add3 (make-adder 3))
+        (def mapped (list.map (list.seq 0 1000) add3))
+        (def filtered (list.filter mapped (fn (x) (== (% x 2) 0))))
+        (list.reduce filtered (fn (acc x) (+ acc x)))
+    )
+    "#;
+
+    c.bench_function("closure map/filter/reduce pipeline", |b| {
+        b.iter_batched(
+            Runtime::new_default,
+            |mut runtime| runtime.eval_string(black_box(prog)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_string_building(c: &mut Criterion) {
+    let prog = r#"
+    (
+        (def out "")
+        (for i (list.seq 0 500) (def out (+ out (str.format "item-%v;" i))))
+        (str.len (str.join (str.split out ";") ","))
+    )
+    "#;
+
+    c.bench_function("string building", |b| {
+        b.iter_batched(
+            Runtime::new_default,
+            |mut runtime| runtime.eval_string(black_box(prog)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_import_chain(c: &mut Criterion) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rssli-bench-import-chain");
+    let entry = generate_import_chain(&dir, 50);
+    let prog = format!("(import \"{}\")", entry.display());
+
+    c.bench_function("deep import chain (50 files)", |b| {
+        b.iter_batched(
+            Runtime::new_default,
+            |mut runtime| runtime.eval_string(black_box(&prog)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_tokenize_parse,
+    bench_fib,
+    bench_closure_pipeline,
+    bench_string_building,
+    bench_import_chain
+);
+criterion_main!(benches);
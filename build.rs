@@ -0,0 +1,36 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "capi")]
+    generate_header();
+}
+
+/// Generates the C header for `src/capi.rs` into `OUT_DIR`. The checked-in
+/// `capi/rssli.h` is only overwritten when `RSSLI_UPDATE_HEADER` is set
+/// (`make -C capi header`), otherwise a stale copy is reported as a warning.
+#[cfg(feature = "capi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=capi/rssli.h");
+    println!("cargo:rerun-if-env-changed=RSSLI_UPDATE_HEADER");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Cannot read cbindgen.toml");
+    let generated = format!("{}/rssli.h", out_dir);
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/capi.rs", crate_dir))
+        .generate()
+        .expect("Cannot generate the C header")
+        .write_to_file(&generated);
+
+    let checked_in = format!("{}/capi/rssli.h", crate_dir);
+    if std::env::var_os("RSSLI_UPDATE_HEADER").is_some() {
+        std::fs::copy(&generated, &checked_in).expect("Cannot update capi/rssli.h");
+    } else if std::fs::read(&generated).ok() != std::fs::read(&checked_in).ok() {
+        println!("cargo:warning=capi/rssli.h is out of date, run `make -C capi header`");
+    }
+}
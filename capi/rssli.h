@@ -0,0 +1,156 @@
+/* C interface of rssli, see src/capi.rs */
+
+#ifndef RSSLI_H
+#define RSSLI_H
+
+/* Generated by cbindgen when building with the `capi` feature, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum RssliType {
+  RSSLI_TYPE_NIL,
+  RSSLI_TYPE_INT,
+  RSSLI_TYPE_FLOAT,
+  RSSLI_TYPE_STRING,
+  RSSLI_TYPE_SYMBOL,
+  RSSLI_TYPE_LIST,
+  RSSLI_TYPE_MAP,
+  RSSLI_TYPE_BOOL,
+  RSSLI_TYPE_FUNC,
+  RSSLI_TYPE_SEQ,
+  RSSLI_TYPE_OBJECT,
+} RssliType;
+
+/**
+ * Interpreter with all builtin modules and the last error message
+ */
+typedef struct RssliRuntime RssliRuntime;
+
+/**
+ * Interpreter value
+ */
+typedef struct RssliValue RssliValue;
+
+/**
+ * Native function called with the evaluated arguments, which are only valid
+ * during the call. Returns a new value, or NULL to fail the call with the
+ * message passed to `rssli_set_callback_error`.
+ */
+typedef struct RssliValue *(*RssliNativeFn)(void *user_data,
+                                            const struct RssliValue *const *args,
+                                            size_t argc);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * New runtime with all builtin modules
+ */
+struct RssliRuntime *rssli_runtime_new(void);
+
+void rssli_runtime_free(struct RssliRuntime *runtime);
+
+/**
+ * Evaluates a program like `Runtime::eval_string`, returns NULL on error
+ */
+struct RssliValue *rssli_eval(struct RssliRuntime *runtime, const char *source);
+
+/**
+ * Calls a function of the runtime with `argc` arguments, returns NULL on error
+ */
+struct RssliValue *rssli_call(struct RssliRuntime *runtime,
+                              const char *name,
+                              const struct RssliValue *const *args,
+                              size_t argc);
+
+/**
+ * Message of the error of the last `rssli_eval`, `rssli_call` or
+ * `rssli_register_fn` on the runtime, NULL when it succeeded.
+ * Valid until the next call on the runtime.
+ */
+const char *rssli_last_error(const struct RssliRuntime *runtime);
+
+/**
+ * Registers a native function, `user_data` is passed to every call
+ */
+bool rssli_register_fn(struct RssliRuntime *runtime,
+                       const char *name,
+                       RssliNativeFn func,
+                       void *user_data);
+
+/**
+ * Error message of a native function about to return NULL
+ */
+void rssli_set_callback_error(const char *message);
+
+struct RssliValue *rssli_value_nil(void);
+
+struct RssliValue *rssli_value_int(int64_t value);
+
+struct RssliValue *rssli_value_float(double value);
+
+struct RssliValue *rssli_value_bool(bool value);
+
+/**
+ * String value, invalid UTF-8 is replaced
+ */
+struct RssliValue *rssli_value_string(const char *value);
+
+/**
+ * List of copies of `len` values
+ */
+struct RssliValue *rssli_value_list(const struct RssliValue *const *items, size_t len);
+
+struct RssliValue *rssli_value_clone(const struct RssliValue *value);
+
+void rssli_value_free(struct RssliValue *value);
+
+enum RssliType rssli_value_type(const struct RssliValue *value);
+
+/**
+ * Value converted to an integer like `cast.int`, 0 for strings that are not numbers
+ */
+int64_t rssli_value_as_int(const struct RssliValue *value);
+
+/**
+ * Value converted to a float like `cast.float`, 0 for strings that are not numbers
+ */
+double rssli_value_as_float(const struct RssliValue *value);
+
+/**
+ * Truthiness of the value
+ */
+bool rssli_value_as_bool(const struct RssliValue *value);
+
+/**
+ * Value as printed by `print`, free with `rssli_string_free`
+ */
+char *rssli_value_to_string(const struct RssliValue *value);
+
+/**
+ * Number of items of a list or entries of a map, 0 for other values
+ */
+size_t rssli_value_len(const struct RssliValue *value);
+
+/**
+ * Copy of a list item, NULL when out of range or not a list
+ */
+struct RssliValue *rssli_value_list_get(const struct RssliValue *value, size_t index);
+
+/**
+ * Copy of a map entry, NULL when missing or not a map
+ */
+struct RssliValue *rssli_value_map_get(const struct RssliValue *value, const char *key);
+
+void rssli_string_free(char *text);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RSSLI_H */
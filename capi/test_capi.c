@@ -0,0 +1,91 @@
+/* Embeds the interpreter through the C interface, run with `make -C capi test` */
+
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "rssli.h"
+
+static RssliValue *add_offset(void *user_data, const RssliValue *const *args, size_t argc) {
+    if (argc != 1) {
+        rssli_set_callback_error("add-offset expects one argument");
+        return NULL;
+    }
+    int64_t offset = *(const int64_t *)user_data;
+    return rssli_value_int(rssli_value_as_int(args[0]) + offset);
+}
+
+static void test_eval(RssliRuntime *runtime) {
+    RssliValue *value = rssli_eval(runtime, "(+ 40 2)");
+    assert(value != NULL);
+    assert(rssli_value_type(value) == RSSLI_TYPE_INT);
+    assert(rssli_value_as_int(value) == 42);
+    rssli_value_free(value);
+
+    value = rssli_eval(runtime, "(map \"name\" \"rssli\" \"version\" 1)");
+    assert(rssli_value_type(value) == RSSLI_TYPE_MAP);
+    assert(rssli_value_len(value) == 2);
+    RssliValue *name = rssli_value_map_get(value, "name");
+    char *text = rssli_value_to_string(name);
+    assert(strcmp(text, "rssli") == 0);
+    rssli_string_free(text);
+    rssli_value_free(name);
+    rssli_value_free(value);
+}
+
+static void test_errors(RssliRuntime *runtime) {
+    assert(rssli_eval(runtime, "(no-such-function 1)") == NULL);
+    assert(strcmp(rssli_last_error(runtime), "Undeclared function: no-such-function") == 0);
+
+    RssliValue *value = rssli_eval(runtime, "nil");
+    assert(rssli_last_error(runtime) == NULL);
+    assert(rssli_value_type(value) == RSSLI_TYPE_NIL);
+    rssli_value_free(value);
+}
+
+static void test_natives(RssliRuntime *runtime) {
+    int64_t offset = 100;
+    assert(rssli_register_fn(runtime, "add-offset", add_offset, &offset));
+
+    RssliValue *value = rssli_eval(runtime, "(add-offset (* 2 3))");
+    assert(rssli_value_as_int(value) == 106);
+    rssli_value_free(value);
+
+    assert(rssli_eval(runtime, "(add-offset 1 2)") == NULL);
+    assert(strcmp(rssli_last_error(runtime), "add-offset expects one argument") == 0);
+}
+
+static void test_call(RssliRuntime *runtime) {
+    rssli_value_free(rssli_eval(runtime, "(fn pick (items keep) (if keep items nil))"));
+
+    RssliValue *items[] = {rssli_value_float(1.5), rssli_value_string("two")};
+    RssliValue *list = rssli_value_list((const RssliValue *const *)items, 2);
+    RssliValue *args[] = {list, rssli_value_bool(true)};
+    RssliValue *value = rssli_call(runtime, "pick", (const RssliValue *const *)args, 2);
+    assert(value != NULL);
+    assert(rssli_value_type(value) == RSSLI_TYPE_LIST);
+    assert(rssli_value_len(value) == 2);
+    RssliValue *item = rssli_value_list_get(value, 0);
+    assert(rssli_value_as_float(item) == 1.5);
+    rssli_value_free(item);
+    rssli_value_free(value);
+
+    assert(rssli_call(runtime, "pick", (const RssliValue *const *)args, 1) == NULL);
+    assert(rssli_last_error(runtime) != NULL);
+
+    rssli_value_free(args[1]);
+    rssli_value_free(list);
+    rssli_value_free(items[1]);
+    rssli_value_free(items[0]);
+}
+
+int main(void) {
+    RssliRuntime *runtime = rssli_runtime_new();
+    test_eval(runtime);
+    test_errors(runtime);
+    test_natives(runtime);
+    test_call(runtime);
+    rssli_runtime_free(runtime);
+    printf("capi tests passed\n");
+    return 0;
+}
@@ -0,0 +1,475 @@
+//! C interface of the interpreter, the header is generated into `capi/rssli.h`
+//! when building with the `capi` feature.
+//!
+//! Runtimes and values are opaque heap objects owned by the caller, every
+//! `RssliRuntime *` and `RssliValue *` returned by a function must be released
+//! with `rssli_runtime_free` / `rssli_value_free`, strings with
+//! `rssli_string_free`. Pointers passed in must be valid (or NULL where noted)
+//! and a runtime must not be used by two threads at the same time.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_void, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{parser::Value, Runtime};
+
+/// Interpreter with all builtin modules and the last error message
+pub struct RssliRuntime {
+    runtime: Runtime,
+    last_error: Option<CString>,
+}
+
+/// Interpreter value
+pub struct RssliValue(Value);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RssliType {
+    Nil,
+    Int,
+    Float,
+    String,
+    Symbol,
+    List,
+    Map,
+    Bool,
+    Func,
+    Seq,
+    Object,
+}
+
+/// Native function called with the evaluated arguments, which are only valid
+/// during the call. Returns a new value, or NULL to fail the call with the
+/// message passed to `rssli_set_callback_error`.
+pub type RssliNativeFn = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    args: *const *const RssliValue,
+    argc: usize,
+) -> *mut RssliValue;
+
+thread_local! {
+    static CALLBACK_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+struct Callback {
+    func: RssliNativeFn,
+    user_data: *mut c_void,
+}
+
+// the runtime calls natives only from the thread using it, keeping
+// `user_data` usable there is up to the caller
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+impl Callback {
+    fn call(&self, name: &str, args: Vec<Value>) -> anyhow::Result<Value> {
+        let args: Vec<RssliValue> = args.into_iter().map(RssliValue).collect();
+        let arg_ptrs: Vec<*const RssliValue> = args.iter().map(|arg| arg as *const _).collect();
+        CALLBACK_ERROR.with(|error| error.borrow_mut().take());
+
+        let result = unsafe { (self.func)(self.user_data, arg_ptrs.as_ptr(), arg_ptrs.len()) };
+        if result.is_null() {
+            let error = CALLBACK_ERROR.with(|error| error.borrow_mut().take());
+            anyhow::bail!(error.unwrap_or_else(|| format!("Native function {} failed", name)));
+        }
+        Ok(unsafe { Box::from_raw(result) }.0)
+    }
+}
+
+fn c_string(text: String) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+unsafe fn str_arg<'a>(text: *const c_char) -> anyhow::Result<&'a str> {
+    if text.is_null() {
+        anyhow::bail!("Unexpected NULL string");
+    }
+    Ok(CStr::from_ptr(text).to_str()?)
+}
+
+fn new_value(value: Value) -> *mut RssliValue {
+    Box::into_raw(Box::new(RssliValue(value)))
+}
+
+impl RssliRuntime {
+    /// Runs `func` recording its error (or panic), returns the value or NULL
+    fn run<F>(&mut self, func: F) -> *mut RssliValue
+    where
+        F: FnOnce(&mut Runtime) -> anyhow::Result<Value>,
+    {
+        self.last_error = None;
+        let runtime = &mut self.runtime;
+        let result = match catch_unwind(AssertUnwindSafe(|| func(runtime))) {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("Interpreter panicked")),
+        };
+        match result {
+            Ok(value) => new_value(value),
+            Err(err) => {
+                self.last_error = Some(c_string(err.to_string()));
+                ptr::null_mut()
+            }
+        }
+    }
+}
+
+/// New runtime with all builtin modules
+#[no_mangle]
+pub extern "C" fn rssli_runtime_new() -> *mut RssliRuntime {
+    Box::into_raw(Box::new(RssliRuntime {
+        runtime: Runtime::new_default(),
+        last_error: None,
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rssli_runtime_free(runtime: *mut RssliRuntime) {
+    if !runtime.is_null() {
+        drop(Box::from_raw(runtime));
+    }
+}
+
+/// Evaluates a program like `Runtime::eval_string`, returns NULL on error
+#[no_mangle]
+pub unsafe extern "C" fn rssli_eval(
+    runtime: *mut RssliRuntime,
+    source: *const c_char,
+) -> *mut RssliValue {
+    let Some(runtime) = runtime.as_mut() else {
+        return ptr::null_mut();
+    };
+    runtime.run(|rt| rt.eval_string(str_arg(source)?))
+}
+
+/// Calls a function of the runtime with `argc` arguments, returns NULL on error
+#[no_mangle]
+pub unsafe extern "C" fn rssli_call(
+    runtime: *mut RssliRuntime,
+    name: *const c_char,
+    args: *const *const RssliValue,
+    argc: usize,
+) -> *mut RssliValue {
+    let Some(runtime) = runtime.as_mut() else {
+        return ptr::null_mut();
+    };
+    runtime.run(|rt| {
+        let args = values_arg(args, argc)?;
+        rt.call(str_arg(name)?, &args)
+    })
+}
+
+unsafe fn values_arg(values: *const *const RssliValue, len: usize) -> anyhow::Result<Vec<Value>> {
+    if len == 0 {
+        return Ok(vec![]);
+    }
+    if values.is_null() {
+        anyhow::bail!("Unexpected NULL values");
+    }
+    std::slice::from_raw_parts(values, len)
+        .iter()
+        .map(|value| match value.as_ref() {
+            Some(value) => Ok(value.0.clone()),
+            None => anyhow::bail!("Unexpected NULL value"),
+        })
+        .collect()
+}
+
+/// Message of the error of the last `rssli_eval`, `rssli_call` or
+/// `rssli_register_fn` on the runtime, NULL when it succeeded.
+/// Valid until the next call on the runtime.
+#[no_mangle]
+pub unsafe extern "C" fn rssli_last_error(runtime: *const RssliRuntime) -> *const c_char {
+    match runtime.as_ref().and_then(|rt| rt.last_error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Registers a native function, `user_data` is passed to every call
+#[no_mangle]
+pub unsafe extern "C" fn rssli_register_fn(
+    runtime: *mut RssliRuntime,
+    name: *const c_char,
+    func: RssliNativeFn,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(runtime) = runtime.as_mut() else {
+        return false;
+    };
+    runtime.last_error = None;
+    let name = match str_arg(name) {
+        Ok(name) => name.to_owned(),
+        Err(err) => {
+            runtime.last_error = Some(c_string(err.to_string()));
+            return false;
+        }
+    };
+    let callback = Callback { func, user_data };
+    let fn_name = name.clone();
+    runtime.runtime.register_fn(&name, move |args, fenv| {
+        let args = fenv.eval_args(args)?;
+        callback.call(&fn_name, args)
+    });
+    true
+}
+
+/// Error message of a native function about to return NULL
+#[no_mangle]
+pub unsafe extern "C" fn rssli_set_callback_error(message: *const c_char) {
+    let message = match message.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(message).to_string_lossy().into_owned()),
+    };
+    CALLBACK_ERROR.with(|error| *error.borrow_mut() = message);
+}
+
+#[no_mangle]
+pub extern "C" fn rssli_value_nil() -> *mut RssliValue {
+    new_value(Value::Nil)
+}
+
+#[no_mangle]
+pub extern "C" fn rssli_value_int(value: i64) -> *mut RssliValue {
+    new_value(Value::Int(value))
+}
+
+#[no_mangle]
+pub extern "C" fn rssli_value_float(value: f64) -> *mut RssliValue {
+    new_value(Value::Float(value))
+}
+
+#[no_mangle]
+pub extern "C" fn rssli_value_bool(value: bool) -> *mut RssliValue {
+    new_value(Value::Bool(value))
+}
+
+/// String value, invalid UTF-8 is replaced
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_string(value: *const c_char) -> *mut RssliValue {
+    if value.is_null() {
+        return ptr::null_mut();
+    }
+    new_value(Value::String(
+        CStr::from_ptr(value).to_string_lossy().into_owned(),
+    ))
+}
+
+/// List of copies of `len` values
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_list(
+    items: *const *const RssliValue,
+    len: usize,
+) -> *mut RssliValue {
+    match values_arg(items, len) {
+        Ok(items) => new_value(Value::List(items)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_clone(value: *const RssliValue) -> *mut RssliValue {
+    match value.as_ref() {
+        Some(value) => new_value(value.0.clone()),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_free(value: *mut RssliValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_type(value: *const RssliValue) -> RssliType {
+    match value.as_ref().map(|value| &value.0) {
+        Some(Value::Int(_)) => RssliType::Int,
+        Some(Value::Float(_)) => RssliType::Float,
+        Some(Value::String(_)) => RssliType::String,
+        Some(Value::Symbol(_)) => RssliType::Symbol,
+        Some(Value::List(_)) => RssliType::List,
+        Some(Value::Map(_)) => RssliType::Map,
+        Some(Value::Bool(_)) => RssliType::Bool,
+        Some(Value::Func(_)) => RssliType::Func,
+        Some(Value::Seq(_)) => RssliType::Seq,
+        Some(Value::Object(_)) => RssliType::Object,
+        Some(Value::Nil) | None => RssliType::Nil,
+    }
+}
+
+/// Value converted to an integer like `cast.int`, 0 for strings that are not numbers
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_as_int(value: *const RssliValue) -> i64 {
+    match value.as_ref().map(|value| &value.0) {
+        // `Value::as_int` panics on strings that are not numbers
+        Some(Value::String(text)) => text.trim().parse().unwrap_or(0),
+        Some(value) => value.as_int(),
+        None => 0,
+    }
+}
+
+/// Value converted to a float like `cast.float`, 0 for strings that are not numbers
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_as_float(value: *const RssliValue) -> f64 {
+    match value.as_ref().map(|value| &value.0) {
+        Some(Value::String(text)) => text.trim().parse().unwrap_or(0.0),
+        Some(value) => value.as_float(),
+        None => 0.0,
+    }
+}
+
+/// Truthiness of the value
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_as_bool(value: *const RssliValue) -> bool {
+    value.as_ref().is_some_and(|value| value.0.as_bool())
+}
+
+/// Value as printed by `print`, free with `rssli_string_free`
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_to_string(value: *const RssliValue) -> *mut c_char {
+    match value.as_ref() {
+        Some(value) => c_string(value.0.as_string()).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Number of items of a list or entries of a map, 0 for other values
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_len(value: *const RssliValue) -> usize {
+    match value.as_ref().map(|value| &value.0) {
+        Some(Value::List(items)) => items.len(),
+        Some(Value::Map(entries)) => entries.len(),
+        _ => 0,
+    }
+}
+
+/// Copy of a list item, NULL when out of range or not a list
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_list_get(
+    value: *const RssliValue,
+    index: usize,
+) -> *mut RssliValue {
+    match value.as_ref().map(|value| &value.0) {
+        Some(Value::List(items)) if index < items.len() => new_value(items[index].clone()),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Copy of a map entry, NULL when missing or not a map
+#[no_mangle]
+pub unsafe extern "C" fn rssli_value_map_get(
+    value: *const RssliValue,
+    key: *const c_char,
+) -> *mut RssliValue {
+    let (Some(Value::Map(entries)), Ok(key)) = (value.as_ref().map(|value| &value.0), str_arg(key))
+    else {
+        return ptr::null_mut();
+    };
+    match entries.get(key) {
+        Some(entry) => new_value(entry.clone()),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rssli_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take_string(text: *mut c_char) -> String {
+        let result = CStr::from_ptr(text).to_string_lossy().into_owned();
+        rssli_string_free(text);
+        result
+    }
+
+    unsafe extern "C" fn add_offset(
+        user_data: *mut c_void,
+        args: *const *const RssliValue,
+        argc: usize,
+    ) -> *mut RssliValue {
+        if argc != 1 {
+            rssli_set_callback_error(c"add-offset expects one argument".as_ptr());
+            return ptr::null_mut();
+        }
+        let offset = *(user_data as *const i64);
+        rssli_value_int(rssli_value_as_int(*args) + offset)
+    }
+
+    #[test]
+    fn test_eval_and_values() {
+        unsafe {
+            let runtime = rssli_runtime_new();
+            let value = rssli_eval(runtime, c"(fn items () (1 2.5 \"three\"))".as_ptr());
+            assert!(rssli_last_error(runtime).is_null());
+            rssli_value_free(value);
+
+            // unlike `rssli_eval`, `rssli_call` does not unwrap list results
+            let value = rssli_call(runtime, c"items".as_ptr(), ptr::null(), 0);
+            assert_eq!(rssli_value_type(value), RssliType::List);
+            assert_eq!(rssli_value_len(value), 3);
+
+            let item = rssli_value_list_get(value, 1);
+            assert_eq!(rssli_value_type(item), RssliType::Float);
+            assert_eq!(rssli_value_as_float(item), 2.5);
+            rssli_value_free(item);
+            assert_eq!(take_string(rssli_value_to_string(value)), "(1, 2.5, three)");
+            assert!(rssli_value_list_get(value, 3).is_null());
+            rssli_value_free(value);
+
+            let map = rssli_eval(runtime, c"(map \"a\" 1)".as_ptr());
+            let entry = rssli_value_map_get(map, c"a".as_ptr());
+            assert_eq!(rssli_value_as_int(entry), 1);
+            assert!(rssli_value_map_get(map, c"b".as_ptr()).is_null());
+            rssli_value_free(entry);
+            rssli_value_free(map);
+
+            assert!(rssli_eval(runtime, c"(undefined-fn)".as_ptr()).is_null());
+            let error = CStr::from_ptr(rssli_last_error(runtime));
+            assert_eq!(error.to_str().unwrap(), "Undeclared function: undefined-fn");
+            rssli_runtime_free(runtime);
+        }
+    }
+
+    #[test]
+    fn test_native_callbacks_and_call() {
+        unsafe {
+            let runtime = rssli_runtime_new();
+            let mut offset: i64 = 100;
+            let user_data = &mut offset as *mut i64 as *mut c_void;
+            assert!(rssli_register_fn(
+                runtime,
+                c"add-offset".as_ptr(),
+                add_offset,
+                user_data
+            ));
+
+            let value = rssli_eval(runtime, c"(add-offset (+ 1 2))".as_ptr());
+            assert_eq!(rssli_value_as_int(value), 103);
+            rssli_value_free(value);
+
+            assert!(rssli_eval(runtime, c"(add-offset)".as_ptr()).is_null());
+            let error = CStr::from_ptr(rssli_last_error(runtime));
+            assert_eq!(error.to_str().unwrap(), "add-offset expects one argument");
+
+            rssli_eval(runtime, c"(fn twice (x) (* x 2))".as_ptr());
+            let arg = rssli_value_int(21);
+            let args = [arg as *const RssliValue];
+            let value = rssli_call(runtime, c"twice".as_ptr(), args.as_ptr(), 1);
+            assert_eq!(rssli_value_as_int(value), 42);
+            rssli_value_free(value);
+            rssli_value_free(arg);
+            rssli_runtime_free(runtime);
+        }
+    }
+}
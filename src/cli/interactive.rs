@@ -7,7 +7,7 @@ use crate::{corelib::ProgramExitError, parser::Value, Runtime};
  * This will read the input from the user and evaluate it
  * It will return the result of the evaluation
  */
-pub(crate) fn process_interactive() -> anyhow::Result<Value> {
+pub(crate) fn process_interactive(optimize: bool) -> anyhow::Result<Value> {
     // 1. Create the editor
     let mut rl = DefaultEditor::new()?;
 
@@ -18,6 +18,7 @@ pub(crate) fn process_interactive() -> anyhow::Result<Value> {
     }
 
     let mut runtime = Runtime::new_default();
+    runtime.set_optimize(optimize);
 
     loop {
         // 3. Use readline instead of stdin().read_line()
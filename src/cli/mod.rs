@@ -25,25 +25,35 @@ pub fn cli_main() -> anyhow::Result<()> {
 
     match args.command {
         Commands::Eval { expression } => {
-            let result = process_expression(&expression)?;
+            let result = process_expression(&expression, args.optimize)?;
             log::info!("result for '{}': {:?}", expression, result);
         }
-        Commands::File { file } => {
-            let result = process_file(&file)?;
+        Commands::File { file, args: script_args } => {
+            let result = process_file(&file, script_args, args.optimize)?;
+            log::info!("result for '{}': {:?}", file, result);
+        }
+        Commands::Script(mut argv) => {
+            // rssli script.lsp [args...], e.g. from a #!/usr/bin/env rssli line
+            let file = argv.remove(0);
+            let result = process_file(&file, argv, args.optimize)?;
             log::info!("result for '{}': {:?}", file, result);
         }
         Commands::Interactive => {
-            process_interactive()?;
+            process_interactive(args.optimize)?;
         }
     };
 
     Ok(())
 }
 
-fn process_file(file: &str) -> anyhow::Result<Value> {
-    log::info!("Processing file {}", file);
-    let content = fs::read_to_string(file)?;
+fn process_file(file: &str, args: Vec<String>, optimize: bool) -> anyhow::Result<Value> {
+    log::info!("Processing file {} with arguments {:?}", file, args);
+    let content = fs::read_to_string(file)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", file, e))?;
     let mut runtime = Runtime::new_default();
+    runtime.set_optimize(optimize);
+    runtime.set_script(file);
+    runtime.set_args(args);
     match runtime.eval_file(file, &content) {
         Ok(result) => Ok(result),
         Err(err) => {
@@ -56,9 +66,10 @@ fn process_file(file: &str) -> anyhow::Result<Value> {
     }
 }
 
-fn process_expression(expression: &str) -> anyhow::Result<Value> {
+fn process_expression(expression: &str, optimize: bool) -> anyhow::Result<Value> {
     log::info!("Processing expression {}", expression);
     let mut runtime = Runtime::new_default();
+    runtime.set_optimize(optimize);
     match runtime.eval_string(expression) {
         Ok(result) => Ok(result),
         Err(err) => {
@@ -76,6 +87,10 @@ fn process_expression(expression: &str) -> anyhow::Result<Value> {
 pub struct Args {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Fold constant expressions and prune constant branches before evaluation
+    #[arg(long, global = true)]
+    pub optimize: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -89,7 +104,13 @@ pub enum Commands {
     File {
         /// The file to evaluate
         file: String,
+        /// Arguments for the script (after --), returned by sys.args
+        #[arg(last = true)]
+        args: Vec<String>,
     },
     /// Start an interactive session
     Interactive,
+    /// Any other first argument is a script to evaluate, followed by its arguments
+    #[command(external_subcommand)]
+    Script(Vec<String>),
 }
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{func::NativeFn, object::Object, parser::Value, sync::MaybeSendSync};
+
+/**
+ * Strict conversion from an interpreter value to a Rust type.
+ * Unlike the `From<&Value>` coercions in `parser`, a value of the wrong
+ * type is an error instead of a best-effort conversion.
+ */
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> anyhow::Result<Self>;
+
+    /// Value used when the argument is not passed at all, `None` if it is required
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Return types of typed natives: plain values or `anyhow::Result` of them
+pub trait IntoResult {
+    fn into_result(self) -> anyhow::Result<Value>;
+}
+
+impl<T: IntoValue> IntoResult for T {
+    fn into_result(self) -> anyhow::Result<Value> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue> IntoResult for anyhow::Result<T> {
+    fn into_result(self) -> anyhow::Result<Value> {
+        self.map(IntoValue::into_value)
+    }
+}
+
+fn type_error<T>(expected: &str, value: &Value) -> anyhow::Result<T> {
+    anyhow::bail!(
+        "expected {}, got {} ({})",
+        expected,
+        value.type_name(),
+        value
+    )
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Int(x) => Ok(*x),
+            _ => type_error("integer", value),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Float(x) => Ok(*x),
+            Value::Int(x) => Ok(*x as f64),
+            _ => type_error("float", value),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::String(x) => Ok(x.clone()),
+            _ => type_error("string", value),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Bool(x) => Ok(*x),
+            _ => type_error("bool", value),
+        }
+    }
+}
+
+impl FromValue for Object {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Object(x) => Ok(x.clone()),
+            _ => type_error("object", value),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::List(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    T::from_value(item).map_err(|e| anyhow::anyhow!("list item {}: {}", i, e))
+                })
+                .collect(),
+            Value::Nil => Ok(Vec::new()),
+            _ => type_error("list", value),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Map(items) => items
+                .iter()
+                .map(|(k, v)| {
+                    T::from_value(v)
+                        .map(|v| (k.clone(), v))
+                        .map_err(|e| anyhow::anyhow!("map key {:?}: {}", k, e))
+                })
+                .collect(),
+            Value::Nil => Ok(HashMap::new()),
+            _ => type_error("map", value),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Value {
+        Value::Int(self as i64)
+    }
+}
+
+impl IntoValue for usize {
+    fn into_value(self) -> Value {
+        Value::Int(self as i64)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for Object {
+    fn into_value(self) -> Value {
+        Value::Object(self)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, IntoValue::into_value)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Map(
+            self.into_iter()
+                .map(|(k, v)| (k, v.into_value()))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+}
+
+/**
+ * Rust closures callable from scripts with automatic conversion of their
+ * arguments and result. Implemented for `Fn` with up to 6 arguments whose
+ * types implement `FromValue` and whose result implements `IntoResult`.
+ */
+pub trait TypedNative<Args>: MaybeSendSync + 'static {
+    fn arity() -> usize;
+    fn call_typed(&self, name: &str, args: &[Value]) -> anyhow::Result<Value>;
+}
+
+fn arg_at<T: FromValue>(name: &str, args: &[Value], pos: usize) -> anyhow::Result<T> {
+    match args.get(pos) {
+        Some(value) => T::from_value(value)
+            .map_err(|e| anyhow::anyhow!("{}: argument {}: {}", name, pos + 1, e)),
+        None => T::from_missing()
+            .ok_or_else(|| anyhow::anyhow!("{}: missing argument {}", name, pos + 1)),
+    }
+}
+
+macro_rules! impl_typed_native {
+    ($($arg:ident),*) => {
+        impl<Func, Ret, $($arg,)*> TypedNative<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + MaybeSendSync + 'static,
+            Ret: IntoResult,
+            $($arg: FromValue,)*
+        {
+            fn arity() -> usize {
+                0 $(+ { let _ = stringify!($arg); 1 })*
+            }
+
+            #[allow(unused_variables, unused_mut, unused_assignments, non_snake_case)]
+            fn call_typed(&self, name: &str, args: &[Value]) -> anyhow::Result<Value> {
+                let mut pos = 0;
+                $(
+                    let $arg = arg_at::<$arg>(name, args, pos)?;
+                    pos += 1;
+                )*
+                (self)($($arg),*).into_result()
+            }
+        }
+    };
+}
+
+impl_typed_native!();
+impl_typed_native!(A);
+impl_typed_native!(A, B);
+impl_typed_native!(A, B, C);
+impl_typed_native!(A, B, C, D);
+impl_typed_native!(A, B, C, D, E);
+impl_typed_native!(A, B, C, D, E, F);
+
+/// Wraps a typed function as a native evaluating and checking its arguments
+pub(crate) fn typed_native<Args, F>(
+    name: &str,
+    func: F,
+) -> impl NativeFn + 'static
+where
+    F: TypedNative<Args>,
+{
+    let name = name.to_owned();
+    move |args, fenv| {
+        let arity = F::arity();
+        if args.len() > arity {
+            anyhow::bail!(
+                "{}: expected at most {} argument(s), got {}",
+                name,
+                arity,
+                args.len()
+            );
+        }
+        let evl = fenv.eval_args(args)?;
+        func.call_typed(&name, &evl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_register_typed_functions() {
+        let mut runtime = Runtime::new_default();
+        runtime.register("add", |a: i64, b: i64| a + b);
+        runtime.register("greet", |name: String| format!("hello {}", name));
+        runtime.register("avg", |xs: Vec<f64>| {
+            xs.iter().sum::<f64>() / xs.len() as f64
+        });
+        runtime.register("half", || 0.5);
+
+        assert_eq!(runtime.eval_string("(add 40 2)").unwrap(), Value::Int(42));
+        assert_eq!(
+            runtime.eval_string(r#"(greet "world")"#).unwrap(),
+            Value::String("hello world".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(avg (list 1 2.5 4.5 2))").unwrap(),
+            Value::Float(2.5)
+        );
+        assert_eq!(runtime.eval_string("(half)").unwrap(), Value::Float(0.5));
+    }
+
+    #[test]
+    fn test_typed_function_errors() {
+        let mut runtime = Runtime::new_default();
+        runtime.register("add", |a: i64, b: i64| a + b);
+
+        let err = runtime.eval_string(r#"(add 1 "2")"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "add: argument 2: expected integer, got string (2)"
+        );
+        let err = runtime.eval_string("(add 1)").unwrap_err();
+        assert_eq!(err.to_string(), "add: missing argument 2");
+        let err = runtime.eval_string("(add 1 2 3)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "add: expected at most 2 argument(s), got 3"
+        );
+    }
+
+    #[test]
+    fn test_typed_optional_and_fallible() {
+        let mut runtime = Runtime::new_default();
+        runtime.register("pad", |s: String, width: Option<i64>| {
+            format!("{:>1$}", s, width.unwrap_or(5) as usize)
+        });
+        runtime.register("checked-div", |a: i64, b: i64| -> anyhow::Result<i64> {
+            a.checked_div(b)
+                .ok_or_else(|| anyhow::anyhow!("division by zero"))
+        });
+
+        assert_eq!(
+            runtime.eval_string(r#"(pad "ab")"#).unwrap(),
+            Value::String("   ab".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(pad "ab" 3)"#).unwrap(),
+            Value::String(" ab".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(checked-div 7 2)").unwrap(),
+            Value::Int(3)
+        );
+        assert!(runtime.eval_string("(checked-div 7 0)").is_err());
+    }
+
+    #[test]
+    fn test_typed_maps() {
+        let mut runtime = Runtime::new_default();
+        runtime.register("total", |prices: HashMap<String, i64>| {
+            prices.values().sum::<i64>()
+        });
+        runtime.register("inventory", || {
+            HashMap::from([("apples".to_string(), 3_i64), ("pears".to_string(), 5)])
+        });
+
+        assert_eq!(
+            runtime.eval_string(r#"(total (map "a" 1 "b" 2))"#).unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            runtime.eval_string("(total (inventory))").unwrap(),
+            Value::Int(8)
+        );
+        let err = runtime.eval_string(r#"(total (map "a" "x"))"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"total: argument 1: map key "a": expected integer, got string (x)"#
+        );
+    }
+}
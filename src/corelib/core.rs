@@ -11,9 +11,13 @@ pub(crate) fn register(env: &mut Environment) {
     env.add_native("for", cycle_for, true);
 }
 
-// Core
-fn bi_func_def(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
-    let (name, start_from) = if args[0].is_list() {
+/// Builds the closure described by `fn` arguments: `[name] (params) body`.
+/// Returns "anonymous" as name when it is omitted.
+pub(crate) fn make_closure(args: &[Value], fenv: &Environment) -> anyhow::Result<(String, FuncKind)> {
+    let first = args
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Function definition missing arguments list"))?;
+    let (name, start_from) = if first.is_list() {
         ("anonymous".to_owned(), 0)
     } else {
         (args[0].as_string(), 1)
@@ -38,7 +42,12 @@ fn bi_func_def(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value>
         body: Box::new(body),
     };
 
-    let kind = FuncKind::Closure(func, fenv.clone());
+    Ok((name, FuncKind::Closure(func, fenv.clone())))
+}
+
+// Core
+fn bi_func_def(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (name, kind) = make_closure(args, fenv)?;
 
     if name == "anonymous" {
         return Ok(Value::Func(kind));
@@ -110,11 +119,21 @@ fn cycle_while(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value>
 
 /**
  * (for (i) (list.seq 1 10) (body) )
+ * Lazy sequences are iterated without materializing them: (for i (seq.range) (body))
  */
 fn cycle_for(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
     let it = args[0].as_string();
-    let seq = fenv.eval(&args[1])?.as_list();
-    for i in &seq {
+    let seq = fenv.eval(&args[1])?;
+    if let Value::Seq(seq) = seq {
+        let mut items = seq.iter();
+        while let Some(i) = items.next(fenv)? {
+            fenv.vars.set(&it, &i);
+            fenv.eval(&args[2])?;
+        }
+        return Ok(Value::Nil);
+    }
+
+    for i in &seq.as_list() {
         fenv.vars.set(&it, i);
         fenv.eval(&args[2])?;
     }
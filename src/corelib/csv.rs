@@ -0,0 +1,321 @@
+use std::{collections::BTreeMap, fs};
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("csv.parse", csv_parse, false);
+    env.add_native("csv.read", csv_read, false);
+    env.add_native("csv.stringify", csv_stringify, false);
+    env.add_native("csv.write", csv_write, false);
+}
+
+/// Options map shared by all csv functions, unknown keys are rejected
+struct Options {
+    delimiter: u8,
+    /// Reading: the first row names the columns. Writing: the column order.
+    header: Value,
+}
+
+impl Options {
+    fn eval(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<Self> {
+        let mut options = Options {
+            delimiter: b',',
+            header: Value::Nil,
+        };
+        let entries = match arg {
+            Some(arg) => match fenv.eval(arg)? {
+                Value::Map(entries) => entries,
+                Value::Nil => BTreeMap::new(),
+                other => anyhow::bail!("Expected an options map, got {}", other.type_name()),
+            },
+            None => BTreeMap::new(),
+        };
+        for (key, value) in entries {
+            match key.as_str() {
+                "delimiter" => {
+                    let delimiter = value.as_string();
+                    match delimiter.as_bytes() {
+                        [byte] if byte.is_ascii() => options.delimiter = *byte,
+                        _ => anyhow::bail!("Delimiter must be a single ASCII character"),
+                    }
+                }
+                "header" => options.header = value,
+                _ => anyhow::bail!("Unknown csv option: {}", key),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse(text: &str, options: &Options) -> anyhow::Result<Value> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| anyhow::anyhow!("Invalid CSV: {}", e))?;
+        rows.push(record.iter().map(str::to_owned).collect::<Vec<_>>());
+    }
+    if !options.header.as_bool() {
+        return Ok(Value::List(
+            rows.into_iter()
+                .map(|row| Value::List(row.into_iter().map(Value::String).collect()))
+                .collect(),
+        ));
+    }
+
+    let mut rows = rows.into_iter();
+    let header = rows.next().unwrap_or_default();
+    Ok(Value::List(
+        rows.map(|row| {
+            let mut fields = row.into_iter();
+            Value::Map(
+                header
+                    .iter()
+                    .map(|name| (name.clone(), fields.next().map_or(Value::Nil, Value::String)))
+                    .collect(),
+            )
+        })
+        .collect(),
+    ))
+}
+
+fn eval_rows(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<Vec<Value>> {
+    match arg {
+        Some(arg) => match fenv.eval(arg)? {
+            Value::Seq(seq) => seq.collect(fenv),
+            Value::List(rows) if rows.is_empty() => Ok(rows),
+            // function calls unwrap single item lists, so a lone row arrives
+            // as the row itself
+            Value::List(fields) if !fields.iter().any(|f| f.is_list() || f.is_map()) => {
+                Ok(vec![Value::List(fields)])
+            }
+            Value::List(rows) => Ok(rows),
+            row @ Value::Map(_) => Ok(vec![row]),
+            other => anyhow::bail!("Expected a list of rows, got {}", other.type_name()),
+        },
+        None => anyhow::bail!("Expected a list of rows"),
+    }
+}
+
+fn field(value: &Value) -> String {
+    match value {
+        Value::Nil => String::new(),
+        value => value.as_string(),
+    }
+}
+
+fn stringify(rows: &[Value], options: &Options) -> anyhow::Result<String> {
+    let mut writer = ::csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_writer(Vec::new());
+    let header = match (&options.header, rows.first()) {
+        (Value::List(names), _) => Some(names.iter().map(field).collect::<Vec<_>>()),
+        (Value::Bool(true), Some(Value::Map(first))) => Some(first.keys().cloned().collect()),
+        _ => None,
+    };
+    if let Some(header) = &header {
+        writer.write_record(header)?;
+    }
+    for row in rows {
+        match row {
+            Value::Map(entries) => {
+                let Some(header) = &header else {
+                    anyhow::bail!("Map rows need a header");
+                };
+                writer.write_record(
+                    header
+                        .iter()
+                        .map(|name| entries.get(name).map_or_else(String::new, field)),
+                )?;
+            }
+            Value::List(fields) => writer.write_record(fields.iter().map(field))?,
+            other => anyhow::bail!("Expected a row, got {}", other.type_name()),
+        }
+    }
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e.error()))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/**
+ * Usage: (csv.parse text [options])
+ * Returns the rows as lists of strings. Options is a map with "delimiter"
+ * (default ",") and "header": when true the first row names the columns
+ * and the other rows become maps, missing fields are nil.
+ */
+fn csv_parse(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let text = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a CSV string"),
+    };
+    let options = Options::eval(args.get(1), fenv)?;
+    parse(&text, &options)
+}
+
+/**
+ * Usage: (csv.read path [options])
+ * Like csv.parse on the content of the file, checked by the sandbox.
+ */
+fn csv_read(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a path"),
+    };
+    let path = fenv.checked_path(&path)?;
+    let options = Options::eval(args.get(1), fenv)?;
+    let text = fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    parse(&text, &options)
+}
+
+/**
+ * Usage: (csv.stringify rows [options])
+ * Rows are lists or maps, fields are quoted when needed and nil is written
+ * as an empty field. The "header" option is a list of column names, or true
+ * to use the keys of the first map row. Map rows are written in the header
+ * order.
+ */
+fn csv_stringify(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let rows = eval_rows(args.first(), fenv)?;
+    let options = Options::eval(args.get(1), fenv)?;
+    Ok(Value::String(stringify(&rows, &options)?))
+}
+
+/**
+ * Usage: (csv.write path rows [options])
+ * Writes rows like csv.stringify, creating or truncating the file.
+ */
+fn csv_write(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a path"),
+    };
+    let path = fenv.checked_path(&path)?;
+    let rows = eval_rows(args.get(1), fenv)?;
+    let options = Options::eval(args.get(2), fenv)?;
+    fs::write(&path, stringify(&rows, &options)?)
+        .map_err(|e| anyhow::anyhow!("Cannot write {}: {}", path.display(), e))?;
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+
+    fn row(fields: &[&str]) -> Value {
+        Value::List(fields.iter().map(|field| string(field)).collect())
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global(
+            "text",
+            string("name,note\r\nann,\"a, b\"\nbob,\"say \"\"hi\"\"\nthere\"\n"),
+        );
+        assert_eq!(
+            runtime.eval_parsed("(csv.parse text)").unwrap(),
+            Value::List(vec![
+                row(&["name", "note"]),
+                row(&["ann", "a, b"]),
+                row(&["bob", "say \"hi\"\nthere"]),
+            ])
+        );
+
+        runtime.set_global("text", string("a;b\n1;2\n3\n"));
+        assert_eq!(
+            runtime
+                .eval_parsed(r#"(csv.parse text (map "delimiter" ";" "header" true))"#)
+                .unwrap(),
+            Value::List(vec![
+                Value::Map(BTreeMap::from([
+                    ("a".to_owned(), string("1")),
+                    ("b".to_owned(), string("2")),
+                ])),
+                Value::Map(BTreeMap::from([
+                    ("a".to_owned(), string("3")),
+                    ("b".to_owned(), Value::Nil),
+                ])),
+            ])
+        );
+
+        let err = runtime
+            .eval_string(r#"(csv.parse text (map "delimiter" ";;"))"#)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Delimiter must be a single ASCII character");
+    }
+
+    #[test]
+    fn test_stringify() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(
+            runtime
+                .eval_string(r#"(csv.stringify (list ("a" "b, c") (1 nil)))"#)
+                .unwrap(),
+            string("a,\"b, c\"\n1,\n")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(
+                    r#"(csv.stringify (list (map "x" 1 "y" "q") (map "y" 2)) (map "header" true))"#
+                )
+                .unwrap(),
+            string("x,y\n1,q\n,2\n")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(
+                    r#"(csv.stringify (map "x" 1 "y" 2) (map "header" ("y" "x") "delimiter" ";"))"#
+                )
+                .unwrap(),
+            string("y;x\n2;1\n")
+        );
+        assert_eq!(
+            runtime.eval_string("(csv.stringify (list))").unwrap(),
+            string("")
+        );
+        let err = runtime
+            .eval_string(r#"(csv.stringify (list (map "x" 1)))"#)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Map rows need a header");
+    }
+
+    #[test]
+    fn test_read_write() {
+        let path = std::env::temp_dir().join(format!("rssli-csv-{}.csv", std::process::id()));
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("path", string(path.to_str().unwrap()));
+        runtime
+            .eval_string(r#"(csv.write path (list ("id" "name") (1 "a \"b\"") (2 "c")))"#)
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "id,name\n1,\"a \"\"b\"\"\"\n2,c\n"
+        );
+        runtime
+            .eval_string(r#"(def rows (csv.read path (map "header" true)))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.get_global("rows"),
+            Some(Value::List(vec![
+                Value::Map(BTreeMap::from([
+                    ("id".to_owned(), string("1")),
+                    ("name".to_owned(), string("a \"b\"")),
+                ])),
+                Value::Map(BTreeMap::from([
+                    ("id".to_owned(), string("2")),
+                    ("name".to_owned(), string("c")),
+                ])),
+            ]))
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,375 @@
+use std::{collections::BTreeMap, fs, io::Write, path::PathBuf, time::UNIX_EPOCH};
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("fs.read", fs_read, false);
+    env.add_native("fs.write", fs_write, false);
+    env.add_native("fs.append", fs_append, false);
+    env.add_native("fs.exists", fs_exists, false);
+    env.add_native("fs.list", fs_list, false);
+    env.add_native("fs.mkdir", fs_mkdir, false);
+    env.add_native("fs.remove", fs_remove, false);
+    env.add_native("fs.copy", fs_copy, false);
+    env.add_native("fs.rename", fs_rename, false);
+    env.add_native("fs.stat", fs_stat, false);
+    env.add_native("fs.glob", fs_glob, false);
+}
+
+/// Evaluates a path argument, resolved like `import` and checked by the sandbox
+fn eval_path(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<PathBuf> {
+    let Some(arg) = arg else {
+        anyhow::bail!("Expected a path");
+    };
+    let path = fenv.eval(arg)?.as_string();
+    fenv.checked_path(&path)
+}
+
+fn eval_content(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<String> {
+    match arg {
+        Some(arg) => Ok(fenv.eval(arg)?.as_string()),
+        None => anyhow::bail!("Expected the content to write"),
+    }
+}
+
+/**
+ * Usage: (fs.read path)
+ * Returns the content of the file as a string.
+ */
+fn fs_read(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    Ok(Value::String(content))
+}
+
+/**
+ * Usage: (fs.write path content)
+ * Creates or truncates the file.
+ */
+fn fs_write(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let content = eval_content(args.get(1), fenv)?;
+    fs::write(&path, content)
+        .map_err(|e| anyhow::anyhow!("Cannot write {}: {}", path.display(), e))?;
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (fs.append path content)
+ * Creates the file if it does not exist.
+ */
+fn fs_append(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let content = eval_content(args.get(1), fenv)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| anyhow::anyhow!("Cannot append to {}: {}", path.display(), e))?;
+    Ok(Value::Nil)
+}
+
+fn fs_exists(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    Ok(Value::Bool(path.exists()))
+}
+
+/**
+ * Usage: (fs.list [dir])
+ * Returns the sorted names of the entries of the directory (default ".").
+ */
+fn fs_list(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = match args.first() {
+        Some(_) => eval_path(args.first(), fenv)?,
+        None => fenv.checked_path(".")?,
+    };
+    let mut names = fs::read_dir(&path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| anyhow::anyhow!("Cannot list {}: {}", path.display(), e))?;
+    names.sort();
+    Ok(Value::List(names.into_iter().map(Value::String).collect()))
+}
+
+/**
+ * Usage: (fs.mkdir path)
+ * Creates the directory and any missing parents.
+ */
+fn fs_mkdir(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    fs::create_dir_all(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot create directory {}: {}", path.display(), e))?;
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (fs.remove path [recursive])
+ * Removes a file or an empty directory, or a directory with its content
+ * when recursive is true.
+ */
+fn fs_remove(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let recursive = match args.get(1) {
+        Some(arg) => fenv.eval(arg)?.as_bool(),
+        None => false,
+    };
+    let result = match fs::symlink_metadata(&path) {
+        Ok(meta) if meta.is_dir() && recursive => fs::remove_dir_all(&path),
+        Ok(meta) if meta.is_dir() => fs::remove_dir(&path),
+        Ok(_) => fs::remove_file(&path),
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| anyhow::anyhow!("Cannot remove {}: {}", path.display(), e))?;
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (fs.copy from to)
+ * Copies a file, returns the number of bytes copied.
+ */
+fn fs_copy(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let from = eval_path(args.first(), fenv)?;
+    let to = eval_path(args.get(1), fenv)?;
+    let copied = fs::copy(&from, &to).map_err(|e| {
+        anyhow::anyhow!("Cannot copy {} to {}: {}", from.display(), to.display(), e)
+    })?;
+    Ok(Value::Int(copied as i64))
+}
+
+fn fs_rename(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let from = eval_path(args.first(), fenv)?;
+    let to = eval_path(args.get(1), fenv)?;
+    fs::rename(&from, &to).map_err(|e| {
+        anyhow::anyhow!(
+            "Cannot rename {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        )
+    })?;
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (fs.stat path)
+ * Returns a map with "size", "is_file", "is_dir", "is_symlink", "readonly"
+ * and "modified" (seconds since the Unix epoch).
+ */
+fn fs_stat(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let error = |e: std::io::Error| anyhow::anyhow!("Cannot stat {}: {}", path.display(), e);
+    let meta = fs::metadata(&path).map_err(error)?;
+    let is_symlink = fs::symlink_metadata(&path).map_err(error)?.is_symlink();
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(Value::Nil, |since| Value::Int(since.as_secs() as i64));
+
+    Ok(Value::Map(BTreeMap::from([
+        ("size".to_owned(), Value::Int(meta.len() as i64)),
+        ("is_file".to_owned(), Value::Bool(meta.is_file())),
+        ("is_dir".to_owned(), Value::Bool(meta.is_dir())),
+        ("is_symlink".to_owned(), Value::Bool(is_symlink)),
+        (
+            "readonly".to_owned(),
+            Value::Bool(meta.permissions().readonly()),
+        ),
+        ("modified".to_owned(), modified),
+    ])))
+}
+
+/**
+ * Usage: (fs.glob pattern)
+ * Returns the sorted paths matching the pattern, e.g. "*.lsp", where "**"
+ * as a path component matches any number of directories.
+ * Relative patterns give paths relative to the current file's directory,
+ * paths the sandbox denies are left out.
+ */
+fn fs_glob(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let pattern = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a glob pattern"),
+    };
+    let base = fenv.base_dir()?;
+    let full_pattern = fenv.resolve_path(&pattern)?;
+    if !fenv.sandbox().fs_allowed() {
+        anyhow::bail!("Filesystem access is denied: {}", pattern);
+    }
+
+    let entries = glob::glob(&full_pattern.to_string_lossy())
+        .map_err(|e| anyhow::anyhow!("Invalid glob pattern {}: {}", pattern, e))?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| anyhow::anyhow!("Cannot read {}", e))?;
+        if fenv.sandbox().check_access(&path).is_err() {
+            continue;
+        }
+        let path = match path.strip_prefix(&base) {
+            Ok(relative) if !PathBuf::from(&pattern).is_absolute() => relative.to_path_buf(),
+            _ => path,
+        };
+        paths.push(Value::String(path.to_string_lossy().into_owned()));
+    }
+    Ok(Value::List(paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rssli-fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_read_write_files() {
+        let dir = temp_dir("files");
+        let script = dir.join("script.lsp");
+        fs::write(
+            &script,
+            r#"(
+                (fs.mkdir "out/nested")
+                (fs.write "out/a.txt" "hello")
+                (fs.append "out/a.txt" " world")
+                (fs.copy "out/a.txt" "out/nested/b.txt")
+                (fs.rename "out/nested/b.txt" "out/c.txt")
+                (fs.read "out/c.txt")
+            )"#,
+        )
+        .unwrap();
+
+        // relative paths are resolved against the directory of the script
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_string(&format!(r#"(import "{}")"#, script.display()))
+            .unwrap();
+        assert_eq!(result, Value::String("hello world".into()));
+        assert_eq!(
+            fs::read_to_string(dir.join("out/a.txt")).unwrap(),
+            "hello world"
+        );
+
+        let out = dir.join("out");
+        runtime.set_global("out", Value::String(out.to_string_lossy().into_owned()));
+        assert_eq!(
+            runtime.eval_parsed("(fs.list out)").unwrap(),
+            Value::List(vec![
+                Value::String("a.txt".into()),
+                Value::String("c.txt".into()),
+                Value::String("nested".into())
+            ])
+        );
+        let stat = runtime
+            .eval_parsed(r#"(fs.stat (str.format "%v/a.txt" out))"#)
+            .unwrap();
+        let Value::Map(stat) = stat else {
+            panic!("expected a map, got {:?}", stat)
+        };
+        assert_eq!(stat["size"], Value::Int(11));
+        assert_eq!(stat["is_file"], Value::Bool(true));
+        assert_eq!(stat["is_dir"], Value::Bool(false));
+
+        assert_eq!(
+            runtime.eval_string("(fs.exists out)").unwrap(),
+            Value::Bool(true)
+        );
+        assert!(runtime.eval_string("(fs.remove out)").is_err());
+        runtime.eval_string("(fs.remove out true)").unwrap();
+        assert_eq!(
+            runtime.eval_string("(fs.exists out)").unwrap(),
+            Value::Bool(false)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = temp_dir("glob");
+        fs::create_dir_all(dir.join("lib/nested")).unwrap();
+        for file in ["main.lsp", "lib/a.lsp", "lib/nested/b.lsp", "lib/notes.txt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join("script.lsp"), r#"(fs.glob "lib/**/*.lsp")"#).unwrap();
+
+        let mut runtime = Runtime::new_default();
+        let script = dir.join("script.lsp");
+        let result = runtime
+            .eval_file(
+                script.to_str().unwrap(),
+                &fs::read_to_string(&script).unwrap(),
+            )
+            .unwrap();
+        // `eval_file` unwraps list results to the last element
+        assert_eq!(result, Value::String("lib/nested/b.lsp".into()));
+
+        let pattern = format!("{}/*.lsp", dir.display());
+        runtime.set_global("pattern", Value::String(pattern));
+        assert_eq!(
+            runtime.eval_parsed("(fs.glob pattern)").unwrap(),
+            Value::List(vec![
+                Value::String(dir.join("main.lsp").to_string_lossy().into_owned()),
+                Value::String(dir.join("script.lsp").to_string_lossy().into_owned()),
+            ])
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let dir = temp_dir("sandbox");
+        let allowed = dir.join("allowed");
+        fs::create_dir_all(&allowed).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .allow_dir(&allowed)
+            .build()
+            .unwrap();
+        runtime.set_global("dir", Value::String(allowed.to_string_lossy().into_owned()));
+        runtime
+            .eval_string(r#"(fs.mkdir (str.format "%v/new" dir))"#)
+            .unwrap();
+        runtime
+            .eval_string(r#"(fs.write (str.format "%v/new/../ok.txt" dir) "ok")"#)
+            .unwrap();
+        assert_eq!(fs::read_to_string(allowed.join("ok.txt")).unwrap(), "ok");
+
+        let err = runtime
+            .eval_string(r#"(fs.read (str.format "%v/../secret.txt" dir))"#)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Access outside of the allowed directories"));
+        let err = runtime
+            .eval_string(r#"(fs.write (str.format "%v/../../x/y.txt" dir) "x")"#)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Access outside of the allowed directories"));
+
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .deny_fs()
+            .build()
+            .unwrap();
+        let err = runtime
+            .eval_string(r#"(fs.exists "anything")"#)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Filesystem access is denied"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,540 @@
+use crate::{
+    env::Environment,
+    parser::Value,
+    seq::{Seq, SeqSource},
+    sync::Lock,
+};
+
+use self::imp::Body;
+
+/// Reserved for the stack of a generator body, pages are committed when used
+const GENERATOR_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("gen", bi_gen, false);
+    env.add_native("yield", bi_yield, false);
+}
+
+/// Call run by the body of a generator, in the environment `gen` was called from
+struct Job {
+    env: Environment,
+    call: Value,
+}
+
+/// The body runs as a coroutine on its own stack, on the thread of its consumer
+#[cfg(not(feature = "sync"))]
+mod imp {
+    use std::cell::RefCell;
+
+    use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult, Yielder};
+
+    use super::{Job, GENERATOR_STACK_SIZE};
+    use crate::parser::Value;
+
+    type BodyYielder = Yielder<(), Value>;
+
+    thread_local! {
+        /// Yielders of the bodies running on this thread, the innermost last
+        static YIELDERS: RefCell<Vec<*const BodyYielder>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Marks a body as running while alive
+    struct Running;
+
+    impl Running {
+        fn enter(yielder: *const BodyYielder) -> Self {
+            YIELDERS.with(|yielders| yielders.borrow_mut().push(yielder));
+            Self
+        }
+    }
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            YIELDERS.with(|yielders| yielders.borrow_mut().pop());
+        }
+    }
+
+    /// Marks a suspended body as running again when dropped, once it is
+    /// resumed or while it unwinds
+    struct Suspended(*const BodyYielder);
+
+    impl Drop for Suspended {
+        fn drop(&mut self) {
+            YIELDERS.with(|yielders| yielders.borrow_mut().push(self.0));
+        }
+    }
+
+    /// Dropping a suspended body unwinds it from its pending `yield`, which
+    /// releases its values
+    pub(super) struct Body(Coroutine<(), Value, anyhow::Result<()>>);
+
+    impl Body {
+        pub(super) fn start(job: Job) -> anyhow::Result<Self> {
+            let stack = DefaultStack::new(GENERATOR_STACK_SIZE)?;
+            Ok(Self(Coroutine::with_stack(stack, move |yielder, ()| {
+                let Job { mut env, call } = job;
+                let _running = Running::enter(yielder);
+                env.eval(&call).map(|_| ())
+            })))
+        }
+
+        /// Runs the body until its next `yield`, `None` once it returned
+        pub(super) fn resume(&mut self) -> anyhow::Result<Option<Value>> {
+            match self.0.resume(()) {
+                CoroutineResult::Yield(value) => Ok(Some(value)),
+                CoroutineResult::Return(result) => result.map(|()| None),
+            }
+        }
+    }
+
+    pub(super) fn yield_value(value: Value) -> anyhow::Result<()> {
+        let Some(yielder) = YIELDERS.with(|yielders| yielders.borrow_mut().pop()) else {
+            anyhow::bail!("yield called outside of a generator");
+        };
+        let _suspended = Suspended(yielder);
+        // SAFETY: bodies push their yielder when they start and pop it when
+        // they return, and around every `yield`, so the innermost one belongs
+        // to the body running this call. It lives on the stack of that body
+        // until the body returns, which cannot happen before this call does.
+        unsafe { &*yielder }.suspend(value);
+        Ok(())
+    }
+}
+
+/// The body runs on its own thread, which waits for a resume request before
+/// running and at every `yield`. Runtimes are `Send` with the `sync` feature,
+/// which coroutines are not.
+#[cfg(feature = "sync")]
+mod imp {
+    use std::{
+        cell::RefCell,
+        sync::mpsc::{self, Receiver, Sender},
+        thread::{self, JoinHandle},
+    };
+
+    use super::{Job, GENERATOR_STACK_SIZE};
+    use crate::parser::Value;
+
+    enum Event {
+        Yielded(Value),
+        Done(anyhow::Result<()>),
+    }
+
+    /// Channels of the body running on the current thread
+    struct Yielder {
+        events: Sender<Event>,
+        /// Disconnected when the body is stopped
+        resume: Receiver<()>,
+    }
+
+    impl Yielder {
+        /// Blocks until the consumer asks for the next value
+        fn wait(&self) -> anyhow::Result<()> {
+            self.resume
+                .recv()
+                .map_err(|_| anyhow::anyhow!("Generator cancelled"))
+        }
+    }
+
+    thread_local! {
+        static YIELDER: RefCell<Option<Yielder>> = const { RefCell::new(None) };
+    }
+
+    /// Dropping a suspended body makes its pending `yield` fail, so it
+    /// unwinds and releases its values, and waits for its thread to exit
+    pub(super) struct Body {
+        resume: Option<Sender<()>>,
+        events: Receiver<Event>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl Body {
+        pub(super) fn start(job: Job) -> anyhow::Result<Self> {
+            let (resume_tx, resume_rx) = mpsc::channel();
+            let (events_tx, events_rx) = mpsc::channel();
+
+            let handle = thread::Builder::new()
+                .name("rssli-gen".to_owned())
+                .stack_size(GENERATOR_STACK_SIZE)
+                .spawn(move || {
+                    let Job { mut env, call } = job;
+                    let events = events_tx.clone();
+                    let yielder = Yielder {
+                        events: events_tx,
+                        resume: resume_rx,
+                    };
+                    let started = yielder.wait();
+                    YIELDER.with(|y| *y.borrow_mut() = Some(yielder));
+                    let result = started.and_then(|()| env.eval(&call).map(|_| ()));
+                    YIELDER.with(|y| y.borrow_mut().take());
+                    // release every shared value before handing control back
+                    drop(call);
+                    drop(env);
+                    let _ = events.send(Event::Done(result));
+                })?;
+
+            Ok(Self {
+                resume: Some(resume_tx),
+                events: events_rx,
+                handle: Some(handle),
+            })
+        }
+
+        /// Runs the body until its next `yield`, `None` once it returned
+        pub(super) fn resume(&mut self) -> anyhow::Result<Option<Value>> {
+            if let Some(resume) = &self.resume {
+                let _ = resume.send(());
+            }
+            match self.events.recv() {
+                Ok(Event::Yielded(value)) => Ok(Some(value)),
+                Ok(Event::Done(result)) => {
+                    self.join();
+                    result.map(|()| None)
+                }
+                Err(_) => {
+                    self.join();
+                    anyhow::bail!("Generator stopped unexpectedly")
+                }
+            }
+        }
+
+        fn join(&mut self) {
+            if let Some(handle) = self.handle.take() {
+                if handle.join().is_err() {
+                    log::error!("Generator thread panicked");
+                }
+            }
+        }
+    }
+
+    impl Drop for Body {
+        fn drop(&mut self) {
+            self.resume = None;
+            self.join();
+        }
+    }
+
+    pub(super) fn yield_value(value: Value) -> anyhow::Result<()> {
+        YIELDER.with(|y| {
+            let yielder = y.borrow();
+            let Some(yielder) = yielder.as_ref() else {
+                anyhow::bail!("yield called outside of a generator");
+            };
+            if yielder.events.send(Event::Yielded(value)).is_err() {
+                anyhow::bail!("Generator consumer is gone");
+            }
+            yielder.wait()
+        })
+    }
+}
+
+enum GeneratorState {
+    NotStarted(Box<Job>),
+    Suspended(Body),
+    /// The body is held by the consumer waiting for the next value
+    Running,
+    Finished,
+}
+
+/// Resumable producer backed by a Lisp function that calls `yield`
+struct Generator {
+    state: Lock<GeneratorState>,
+}
+
+impl SeqSource for Generator {
+    fn next(&self, _env: &mut Environment) -> anyhow::Result<Option<Value>> {
+        // the state is not locked while the body runs, so the body reading
+        // its own generator gets an error
+        let body = match self.state.replace(GeneratorState::Running) {
+            GeneratorState::NotStarted(job) => Body::start(*job),
+            GeneratorState::Suspended(body) => Ok(body),
+            GeneratorState::Running => anyhow::bail!("Generator is already running"),
+            GeneratorState::Finished => {
+                self.state.replace(GeneratorState::Finished);
+                return Ok(None);
+            }
+        };
+        let next = body.and_then(|mut body| Ok(body.resume()?.map(|value| (value, body))));
+        match next {
+            Ok(Some((value, body))) => {
+                self.state.replace(GeneratorState::Suspended(body));
+                Ok(Some(value))
+            }
+            result => {
+                self.state.replace(GeneratorState::Finished);
+                result.map(|_| None)
+            }
+        }
+    }
+
+    fn stop(&self) {
+        let state = {
+            let mut state = self.state.borrow_mut();
+            if matches!(*state, GeneratorState::Running) {
+                // finished by the consumer waiting for it
+                return;
+            }
+            std::mem::replace(&mut *state, GeneratorState::Finished)
+        };
+        // a suspended body unwinds, which must not happen under the lock
+        drop(state);
+    }
+}
+
+/**
+ * Usage: (gen func [args...])
+ * Returns a lazy sequence producing every value passed to `yield` while `func` runs.
+ * The function only runs when the next value is requested.
+ */
+fn bi_gen(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let evl = fenv.eval_args(args)?;
+    let Some(func) = evl.first().filter(|func| func.is_func()) else {
+        anyhow::bail!("gen requires a function");
+    };
+    let call = Value::List(evl.clone());
+    log::debug!("Creating generator for {:?}", func);
+
+    let job = Box::new(Job {
+        env: fenv.clone(),
+        call,
+    });
+    Ok(Value::Seq(Seq::from_stoppable_source(
+        Generator {
+            state: Lock::new(GeneratorState::NotStarted(job)),
+        },
+        fenv,
+    )))
+}
+
+/**
+ * Usage: (yield value)
+ * Suspends the running generator and hands `value` to its consumer.
+ */
+fn bi_yield(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let value = match args.first() {
+        Some(arg) => fenv.eval(arg)?,
+        None => Value::Nil,
+    };
+    imp::yield_value(value)?;
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn ints(values: &[i64]) -> Value {
+        Value::List(values.iter().map(|x| Value::Int(*x)).collect())
+    }
+
+    #[test]
+    fn test_generator_yields_values() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_parsed("(seq.collect (gen (fn () ((yield 1) (yield 2) (yield 3)))))")
+            .unwrap();
+        assert_eq!(result, ints(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_generator_keeps_local_state_between_yields() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(
+                r#"
+            (fn counter (start) (
+                (def n start)
+                (while true (
+                    (yield n)
+                    (def n (+ n 1))
+                ))
+            ))
+            "#,
+            )
+            .unwrap();
+        let result = runtime
+            .eval_parsed("(seq.collect (seq.take (gen counter 5) 4))")
+            .unwrap();
+        assert_eq!(result, ints(&[5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn test_generator_in_for_loop() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_string(
+                r#"
+            (
+                (def total 0)
+                (for x (gen (fn () (for i (list.seq 1 4) (yield (* i 10))))) (def total (+ total x)))
+                total
+            )
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, Value::Int(60));
+    }
+
+    #[test]
+    fn test_generator_with_lazy_functions() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(
+                r#"
+            (fn naturals () (
+                (def i 0)
+                (while true ((yield i) (def i (+ i 1))))
+            ))
+            "#,
+            )
+            .unwrap();
+        let result = runtime
+            .eval_parsed(
+                "(seq.collect (seq.take (seq.filter (gen naturals) (fn (x) (== (% x 3) 0))) 3))",
+            )
+            .unwrap();
+        assert_eq!(result, ints(&[0, 3, 6]));
+    }
+
+    #[test]
+    fn test_generator_is_consumed_once() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def g (gen (fn () ((yield 1) (yield 2)))))")
+            .unwrap();
+        // single element lists are unwrapped by function calls
+        assert_eq!(runtime.eval_parsed("(seq.collect (seq.take g 1))").unwrap(), Value::Int(1));
+        assert_eq!(runtime.eval_parsed("(seq.collect g)").unwrap(), Value::Int(2));
+        assert_eq!(runtime.eval_parsed("(seq.collect g)").unwrap(), ints(&[]));
+    }
+
+    #[test]
+    fn test_nested_generators() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_parsed(
+                r#"
+            (seq.collect (gen (fn ()
+                (for x (gen (fn () ((yield 1) (yield 2)))) (yield (* x 100))))))
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, ints(&[100, 200]));
+    }
+
+    #[test]
+    fn test_generator_errors_propagate() {
+        let mut runtime = Runtime::new_default();
+        assert!(runtime
+            .eval_parsed("(seq.collect (gen (fn () ((yield 1) (undefined-fn)))))")
+            .is_err());
+    }
+
+    #[test]
+    fn test_yield_outside_generator_fails() {
+        let mut runtime = Runtime::new_default();
+        assert!(runtime.eval_string("(yield 1)").is_err());
+    }
+
+    #[test]
+    fn test_abandoned_generator_is_cleaned_up() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def g (gen (fn () (while true (yield 1)))))")
+            .unwrap();
+        runtime.eval_parsed("(seq.collect (seq.take g 2))").unwrap();
+        runtime.eval_string("(undef g)").unwrap();
+        assert_eq!(runtime.eval_string("(+ 1 1)").unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn test_abandoned_nested_generators_are_cleaned_up() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(
+                "(def g (gen (fn () (for x (gen (fn () (while true (yield 1)))) (yield x)))))",
+            )
+            .unwrap();
+        runtime.eval_parsed("(seq.collect (seq.take g 2))").unwrap();
+        runtime.eval_string("(undef g)").unwrap();
+        assert!(runtime.eval_string("(yield 1)").is_err());
+        assert_eq!(
+            runtime
+                .eval_parsed("(seq.collect (gen (fn () ((yield 1) (yield 2)))))")
+                .unwrap(),
+            ints(&[1, 2])
+        );
+    }
+
+    #[test]
+    fn test_generator_reading_itself_fails() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def g (gen (fn () ((yield 1) (yield (seq.collect g))))))")
+            .unwrap();
+        assert_eq!(runtime.eval_parsed("(seq.collect (seq.take g 1))").unwrap(), Value::Int(1));
+        let err = runtime.eval_parsed("(seq.collect g)").unwrap_err();
+        assert!(format!("{:#}", err).contains("Generator is already running"));
+        assert_eq!(runtime.eval_parsed("(seq.collect g)").unwrap(), ints(&[]));
+    }
+
+    #[cfg(all(feature = "sync", target_os = "linux"))]
+    fn generator_threads() -> usize {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+            .filter(|name| name.trim() == "rssli-gen")
+            .count()
+    }
+
+    #[cfg(all(feature = "sync", target_os = "linux"))]
+    #[test]
+    fn test_dropped_runtime_stops_generators() {
+        let before = generator_threads();
+        for _ in 0..20 {
+            let mut runtime = Runtime::new_default();
+            runtime
+                .eval_string("(def g (gen (fn () (while true (yield 1)))))")
+                .unwrap();
+            runtime.eval_parsed("(seq.collect (seq.take g 2))").unwrap();
+        }
+        // other tests may be running generators at the same time
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while generator_threads() > before && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(generator_threads() <= before);
+    }
+
+    #[test]
+    fn test_generators_end_with_their_runtime() {
+        let mut base = Runtime::new_default();
+        base.eval_string("(def g (gen (fn () (while true (yield 1)))))")
+            .unwrap();
+        base.eval_parsed("(seq.collect (seq.take g 1))").unwrap();
+        let mut fork = base.fork();
+        fork.eval_string("(def h (gen (fn () (while true (yield 2)))))")
+            .unwrap();
+        drop(base);
+        assert_eq!(fork.eval_parsed("(seq.collect g)").unwrap(), ints(&[]));
+        assert_eq!(
+            fork.eval_parsed("(seq.collect (seq.take h 2))").unwrap(),
+            ints(&[2, 2])
+        );
+    }
+
+    #[test]
+    fn test_held_generator_ends_with_its_runtime() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def g (gen (fn () (while true (yield 1)))))")
+            .unwrap();
+        runtime.eval_parsed("(seq.collect (seq.take g 1))").unwrap();
+        let Value::Seq(rest) = runtime.eval_parsed("(seq.take g 5)").unwrap() else {
+            panic!("expected a sequence");
+        };
+        drop(runtime);
+        assert_eq!(rest.iter().next(&mut Environment::default()).unwrap(), None);
+    }
+}
@@ -1,17 +1,33 @@
-use std::io::{self, Write};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
 
-use crate::{corelib::strings::strings_format, env::Environment, parser::Value};
+use crate::{
+    corelib::strings::strings_format,
+    env::Environment,
+    object::{Object, ObjectType},
+    parser::Value,
+    seq::{Seq, SeqSource},
+    sync::{Lock, Shared},
+};
 
 pub(crate) fn register(env: &mut Environment) {
     // IO
     env.add_native("print", bi_print, false);
     env.add_native("io.print", bi_print, false);
     env.add_native("io.printf", bi_io_printf, false);
+    env.add_native("io.eprint", bi_io_eprint, false);
     env.add_native("io.readline", bi_io_readline, false);
+    env.add_native("io.lines", bi_io_lines, false);
+    env.add_native("io.read_all", bi_io_read_all, false);
+    env.add_native("io.open", bi_io_open, false);
     env.add_native("log.debug", bi_log_debug, false);
     env.add_native("log.info", bi_log_info, false);
     env.add_native("log.warn", bi_log_warn, false);
     env.add_native("log.error", bi_log_error, false);
+    env.register_object_type("file");
 }
 
 fn bi_print(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
@@ -22,27 +38,244 @@ fn bi_print(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
         .collect();
     let join = parts.join(" ");
     log::trace!("Print: {}", join);
-    println!("{}", join);
+    fenv.streams().write_out(&format!("{}\n", join))?;
+    Ok(Value::String(join))
+}
+
+/**
+ * Usage: (io.eprint value...)
+ * Same as `print`, but writes to the error stream.
+ */
+fn bi_io_eprint(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let parts: Vec<_> = fenv
+        .eval_args(args)?
+        .iter()
+        .map(|x| x.as_string())
+        .collect();
+    let join = parts.join(" ");
+    fenv.streams().write_err(&format!("{}\n", join))?;
     Ok(Value::String(join))
 }
 
 fn bi_io_printf(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
     let processed: Value = strings_format(args, fenv)?;
-    print!("{}", processed.as_string());
-    io::stdout().flush()?;
+    fenv.streams().write_out(&processed.as_string())?;
     Ok(processed)
 }
 
+/**
+ * Usage: (io.readline [prompt])
+ * Reads a line from the input, trimmed. Returns nil at the end of input.
+ */
 fn bi_io_readline(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
     if let Some(prompt) = args.first() {
         let prompt_val = fenv.eval(prompt)?;
-        print!("{} ", prompt_val.as_string());
+        fenv.streams().write_out(&format!("{} ", prompt_val.as_string()))?;
     }
     let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer)?;
+    if fenv.streams().read_line(&mut buffer)? == 0 {
+        return Ok(Value::Nil);
+    }
     Ok(Value::String(buffer.trim().to_owned()))
 }
 
+/**
+ * Usage: (io.lines [file])
+ * Lazy sequence of the lines of the input, or of a file opened with io.open.
+ * Lines are not trimmed, only the line terminator is removed.
+ */
+fn bi_io_lines(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    match args.first() {
+        Some(arg) => Ok(eval_file(arg, fenv)?.lines()),
+        None => Ok(Value::Seq(Seq::from_source(InputLines))),
+    }
+}
+
+/**
+ * Usage: (io.read_all [file])
+ * Reads the rest of the input, or of a file opened with io.open.
+ */
+fn bi_io_read_all(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    if let Some(arg) = args.first() {
+        return eval_file(arg, fenv)?.read_all();
+    }
+    let mut buffer = String::new();
+    fenv.streams().read_all(&mut buffer)?;
+    Ok(Value::String(buffer))
+}
+
+/**
+ * Usage: (io.open path [mode])
+ * Opens a buffered file handle, mode is "r" (default), "w" (truncate) or
+ * "a" (append). Paths are resolved like import and checked by the sandbox.
+ * Readers have the methods read_line, lines and read_all, writers write,
+ * write_line (joins like print) and flush, both close, e.g.
+ * (file.read_line f) or (file.close f).
+ */
+fn bi_io_open(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a path"),
+    };
+    let mode = match args.get(1) {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => "r".to_owned(),
+    };
+    let path = fenv.checked_path(&path)?;
+    let open_error = |e: io::Error| anyhow::anyhow!("Cannot open {}: {}", path.display(), e);
+    let file = match mode.as_str() {
+        "r" => FileKind::Reader(BufReader::new(File::open(&path).map_err(open_error)?)),
+        "w" => FileKind::Writer(BufWriter::new(File::create(&path).map_err(open_error)?)),
+        "a" => {
+            let file = OpenOptions::new().create(true).append(true).open(&path);
+            FileKind::Writer(BufWriter::new(file.map_err(open_error)?))
+        }
+        other => anyhow::bail!("Unknown file mode: {} (expected r, w or a)", other),
+    };
+    let handle = FileHandle {
+        path,
+        file: Shared::new(Lock::new(Some(file))),
+    };
+    Ok(Value::Object(Object::new(file_type(), handle)))
+}
+
+/// Reads a line without its terminator, None at the end of input
+pub(crate) fn read_line<F>(read: F) -> io::Result<Option<String>>
+where
+    F: FnOnce(&mut String) -> io::Result<usize>,
+{
+    let mut line = String::new();
+    if read(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+/// Lines of the runtime input (see `Streams`)
+struct InputLines;
+
+impl SeqSource for InputLines {
+    fn next(&self, env: &mut Environment) -> anyhow::Result<Option<Value>> {
+        Ok(read_line(|buf| env.streams().read_line(buf))?.map(Value::String))
+    }
+}
+
+enum FileKind {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+}
+
+/// Data of `file` objects, `None` once closed
+#[derive(Clone)]
+struct FileHandle {
+    path: PathBuf,
+    file: Shared<Lock<Option<FileKind>>>,
+}
+
+impl FileHandle {
+    fn with_reader<R, F>(&self, func: F) -> anyhow::Result<R>
+    where
+        F: FnOnce(&mut BufReader<File>) -> io::Result<R>,
+    {
+        match self.file.borrow_mut().as_mut() {
+            Some(FileKind::Reader(reader)) => Ok(func(reader)?),
+            Some(FileKind::Writer(_)) => {
+                anyhow::bail!("File {} is not open for reading", self.path.display())
+            }
+            None => anyhow::bail!("File {} is closed", self.path.display()),
+        }
+    }
+
+    fn with_writer<R, F>(&self, func: F) -> anyhow::Result<R>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> io::Result<R>,
+    {
+        match self.file.borrow_mut().as_mut() {
+            Some(FileKind::Writer(writer)) => Ok(func(writer)?),
+            Some(FileKind::Reader(_)) => {
+                anyhow::bail!("File {} is not open for writing", self.path.display())
+            }
+            None => anyhow::bail!("File {} is closed", self.path.display()),
+        }
+    }
+
+    fn read_line(&self) -> anyhow::Result<Value> {
+        let line = self.with_reader(|reader| read_line(|buf| reader.read_line(buf)))?;
+        Ok(line.map_or(Value::Nil, Value::String))
+    }
+
+    fn read_all(&self) -> anyhow::Result<Value> {
+        let mut buffer = String::new();
+        self.with_reader(|reader| reader.read_to_string(&mut buffer))?;
+        Ok(Value::String(buffer))
+    }
+
+    fn lines(&self) -> Value {
+        Value::Seq(Seq::from_source(FileLines(self.clone())))
+    }
+
+    /// Flushes a writer and releases the file, closing again does nothing
+    fn close(&self) -> anyhow::Result<Value> {
+        if let Some(FileKind::Writer(mut writer)) = self.file.take() {
+            writer.flush()?;
+        }
+        Ok(Value::Nil)
+    }
+}
+
+struct FileLines(FileHandle);
+
+impl SeqSource for FileLines {
+    fn next(&self, _env: &mut Environment) -> anyhow::Result<Option<Value>> {
+        match self.0.read_line()? {
+            Value::Nil => Ok(None),
+            line => Ok(Some(line)),
+        }
+    }
+}
+
+fn file_type() -> Shared<ObjectType> {
+    Shared::new(
+        ObjectType::new("file")
+            .with_display(|file: &FileHandle| format!("<file {}>", file.path.display()))
+            .with_method("read_line", |file: &FileHandle, _args, _fenv| file.read_line())
+            .with_method("read_all", |file: &FileHandle, _args, _fenv| file.read_all())
+            .with_method("lines", |file: &FileHandle, _args, _fenv| Ok(file.lines()))
+            .with_method("write", |file: &FileHandle, args, _fenv| {
+                let text: String = args.iter().map(Value::as_string).collect();
+                file.with_writer(|writer| writer.write_all(text.as_bytes()))?;
+                Ok(Value::Nil)
+            })
+            .with_method("write_line", |file: &FileHandle, args, _fenv| {
+                let parts: Vec<_> = args.iter().map(Value::as_string).collect();
+                let line = format!("{}\n", parts.join(" "));
+                file.with_writer(|writer| writer.write_all(line.as_bytes()))?;
+                Ok(Value::Nil)
+            })
+            .with_method("flush", |file: &FileHandle, _args, _fenv| {
+                file.with_writer(|writer| writer.flush())?;
+                Ok(Value::Nil)
+            })
+            .with_method("close", |file: &FileHandle, _args, _fenv| file.close()),
+    )
+}
+
+fn eval_file(arg: &Value, fenv: &mut Environment) -> anyhow::Result<FileHandle> {
+    match fenv.eval(arg)? {
+        Value::Object(obj) => match obj.downcast_ref::<FileHandle>() {
+            Some(file) => Ok(file.clone()),
+            None => anyhow::bail!("Expected a file, got {} object", obj.type_name()),
+        },
+        other => anyhow::bail!("Expected a file, got {}", other.type_name()),
+    }
+}
+
 // Logging
 
 fn bi_log_debug(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
@@ -175,4 +408,125 @@ mod tests {
             .unwrap();
         assert_eq!(result, Value::String("50% and 100%".to_string()));
     }
+
+    #[test]
+    fn test_print_to_captured_output() {
+        let mut runtime = Runtime::new_default();
+        let out = runtime.capture_output();
+        let err = runtime.capture_errors();
+        runtime
+            .eval_string(r#"((print "a" 1) (io.printf "%v-%v" 2 3) (io.eprint "oops"))"#)
+            .unwrap();
+        assert_eq!(out.take(), "a 1\n2-3");
+        assert_eq!(err.contents(), "oops\n");
+
+        runtime.eval_string(r#"(print "again")"#).unwrap();
+        assert_eq!(out.contents(), "again\n");
+    }
+
+    #[test]
+    fn test_readline_from_stdin() {
+        let mut runtime = Runtime::new_default();
+        let out = runtime.capture_output();
+        runtime.set_stdin(std::io::Cursor::new("  first  \nsecond\n"));
+        assert_eq!(
+            runtime.eval_string(r#"(io.readline "?")"#).unwrap(),
+            Value::String("first".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(io.readline)").unwrap(),
+            Value::String("second".to_string())
+        );
+        assert_eq!(out.contents(), "? ");
+    }
+
+    #[test]
+    fn test_readline_returns_nil_at_end_of_input() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_stdin(std::io::Cursor::new("last"));
+        assert_eq!(
+            runtime.eval_string("(io.readline)").unwrap(),
+            Value::String("last".to_string())
+        );
+        assert_eq!(runtime.eval_string("(io.readline)").unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_input_lines_and_read_all() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_stdin(std::io::Cursor::new("  a 1\r\nb 2\n\nc 3\nrest\nof input"));
+        assert_eq!(
+            runtime
+                .eval_parsed("(seq.collect (seq.take (io.lines) 3))")
+                .unwrap(),
+            Value::List(vec![
+                Value::String("  a 1".to_string()),
+                Value::String("b 2".to_string()),
+                Value::String("".to_string())
+            ])
+        );
+        assert_eq!(
+            runtime.eval_string("(io.readline)").unwrap(),
+            Value::String("c 3".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(io.read_all)").unwrap(),
+            Value::String("rest\nof input".to_string())
+        );
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (io.lines))").unwrap(),
+            Value::List(vec![])
+        );
+    }
+
+    #[test]
+    fn test_file_handles() {
+        let dir = std::env::temp_dir().join(format!("rssli-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lines.txt");
+
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("path", Value::String(path.to_string_lossy().into_owned()));
+        runtime
+            .eval_string(
+                r#"(
+                    (def out (io.open path "w"))
+                    (file.write_line out "one")
+                    (file.write_line out "two")
+                    (file.close out)
+                    (def out (io.open path "a"))
+                    (file.write out "three")
+                    (file.close out)
+                )"#,
+            )
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\nthree");
+
+        runtime.eval_string("(def in (io.open path))").unwrap();
+        assert_eq!(
+            runtime.eval_string("(file.read_line in)").unwrap(),
+            Value::String("one".to_string())
+        );
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (io.lines in))").unwrap(),
+            Value::List(vec![
+                Value::String("two".to_string()),
+                Value::String("three".to_string())
+            ])
+        );
+        assert_eq!(runtime.eval_string("(file.read_line in)").unwrap(), Value::Nil);
+        runtime.eval_string("(file.close in)").unwrap();
+        let err = runtime.eval_string("(io.read_all in)").unwrap_err();
+        assert!(err.to_string().ends_with("is closed"));
+        let err = runtime
+            .eval_string(r#"(file.write (io.open path) "x")"#)
+            .unwrap_err();
+        assert!(err.to_string().ends_with("is not open for writing"));
+
+        let mut runtime = Runtime::builder().with_all_modules().deny_fs().build().unwrap();
+        runtime.set_global("path", Value::String(path.to_string_lossy().into_owned()));
+        assert!(runtime.eval_string("(io.open path)").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,165 @@
+use serde_json::Value as Json;
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("json.parse", json_parse, false);
+    env.add_native("json.stringify", json_stringify, false);
+}
+
+fn from_json(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Nil,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Int(i),
+            // floats and integers beyond the i64 range
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(s) => Value::String(s),
+        Json::Array(items) => Value::List(items.into_iter().map(from_json).collect()),
+        Json::Object(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_json(value: &Value, fenv: &mut Environment) -> anyhow::Result<Json> {
+    Ok(match value {
+        Value::Nil => Json::Null,
+        Value::Bool(b) => Json::Bool(*b),
+        Value::Int(i) => Json::from(*i),
+        Value::Float(f) => match serde_json::Number::from_f64(*f) {
+            Some(n) => Json::Number(n),
+            None => anyhow::bail!("Cannot convert {} to JSON", f),
+        },
+        Value::String(s) | Value::Symbol(s) => Json::String(s.clone()),
+        Value::List(items) => Json::Array(
+            items
+                .iter()
+                .map(|item| to_json(item, fenv))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Map(entries) => Json::Object(
+            entries
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), to_json(value, fenv)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Seq(seq) => {
+            let items = seq.collect(fenv)?;
+            to_json(&Value::List(items), fenv)?
+        }
+        Value::Func(_) | Value::Object(_) => {
+            anyhow::bail!("Cannot convert {} to JSON", value.type_name())
+        }
+    })
+}
+
+/**
+ * Usage: (json.parse text)
+ * Objects become maps (sorted by key), arrays lists, null nil. Numbers
+ * without a fraction or exponent become integers when they fit.
+ */
+fn json_parse(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let text = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a JSON string"),
+    };
+    let json: Json =
+        serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("Invalid JSON: {}", e))?;
+    Ok(from_json(json))
+}
+
+/**
+ * Usage: (json.stringify value [pretty])
+ * Finite sequences are written as arrays, functions and objects cannot be
+ * converted. With pretty set, the output is indented by two spaces.
+ */
+fn json_stringify(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let value = match args.first() {
+        Some(arg) => fenv.eval(arg)?,
+        None => anyhow::bail!("Expected a value"),
+    };
+    let pretty = match args.get(1) {
+        Some(arg) => fenv.eval(arg)?.as_bool(),
+        None => false,
+    };
+    let json = to_json(&value, fenv)?;
+    let text = match pretty {
+        true => serde_json::to_string_pretty(&json)?,
+        false => serde_json::to_string(&json)?,
+    };
+    Ok(Value::String(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_parse() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global(
+            "text",
+            Value::String(
+                r#"{"name": "rssli", "tags": ["lisp", 1, 2.5, true, null], "big": 1e3}"#.to_owned(),
+            ),
+        );
+        runtime.eval_string("(def data (json.parse text))").unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(map.get data "name")"#).unwrap(),
+            Value::String("rssli".to_owned())
+        );
+        assert_eq!(
+            runtime.eval_parsed(r#"(map.get data "tags")"#).unwrap(),
+            Value::List(vec![
+                Value::String("lisp".to_owned()),
+                Value::Int(1),
+                Value::Float(2.5),
+                Value::Bool(true),
+                Value::Nil,
+            ])
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(map.get data "big")"#).unwrap(),
+            Value::Float(1000.0)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("text", Value::String("{\n  \"a\": [1, 2,]\n}".to_owned()));
+        let err = runtime.eval_string("(json.parse text)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON: trailing comma at line 2 column 14"
+        );
+    }
+
+    #[test]
+    fn test_stringify() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(
+            runtime
+                .eval_string(r#"(json.stringify (map "b" (seq.range 0 3) "a" nil "c" 1.5))"#)
+                .unwrap(),
+            Value::String(r#"{"a":null,"b":[0,1,2],"c":1.5}"#.to_owned())
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(json.stringify (map "a" "x") true)"#)
+                .unwrap(),
+            Value::String("{\n  \"a\": \"x\"\n}".to_owned())
+        );
+        let err = runtime
+            .eval_string("(json.stringify (fn (x) x))")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Cannot convert function to JSON");
+    }
+}
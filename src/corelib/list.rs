@@ -24,8 +24,16 @@ fn list_create_list(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Va
     Ok(Value::List(values))
 }
 
+/// Evaluates a list argument, `()` evaluates to nil and stands for the empty list
+fn eval_list(arg: &Value, fenv: &mut Environment) -> anyhow::Result<Vec<Value>> {
+    match fenv.eval(arg)? {
+        Value::Nil => Ok(Vec::new()),
+        value => Ok(value.as_list()),
+    }
+}
+
 fn list_filter(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
-    let list = fenv.eval(&args[0])?.as_list();
+    let list = eval_list(&args[0], fenv)?;
     let func = fenv.eval(&args[1])?.as_func();
 
     let mut result = Vec::new();
@@ -62,7 +70,7 @@ fn list_reduce(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value>
 }
 
 fn list_map(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
-    let list = fenv.eval(&args[0])?.as_list();
+    let list = eval_list(&args[0], fenv)?;
     let func = fenv.eval(&args[1])?.as_func();
 
     let mut result = Vec::new();
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("map", map_create, false);
+    env.add_native("map.get", map_get, false);
+    env.add_native("map.set", map_set, false);
+    env.add_native("map.remove", map_remove, false);
+    env.add_native("map.has", map_has, false);
+    env.add_native("map.keys", map_keys, false);
+    env.add_native("map.values", map_values, false);
+    env.add_native("map.len", map_len, false);
+}
+
+fn eval_map(
+    arg: Option<&Value>,
+    fenv: &mut Environment,
+) -> anyhow::Result<BTreeMap<String, Value>> {
+    match arg {
+        Some(arg) => match fenv.eval(arg)? {
+            Value::Map(map) => Ok(map),
+            Value::Nil => Ok(BTreeMap::new()),
+            other => anyhow::bail!("Expected a map, got {}", other.type_name()),
+        },
+        None => anyhow::bail!("Expected a map"),
+    }
+}
+
+fn eval_key(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<String> {
+    match arg {
+        Some(arg) => Ok(fenv.eval(arg)?.as_string()),
+        None => anyhow::bail!("Expected a key"),
+    }
+}
+
+/**
+ * Usage: (map [key value]...)
+ * Keys are converted to strings.
+ */
+fn map_create(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let evl = fenv.eval_args(args)?;
+    if evl.len() % 2 != 0 {
+        anyhow::bail!("map requires key value pairs, got {} arguments", evl.len());
+    }
+    Ok(Value::Map(
+        evl.chunks(2)
+            .map(|pair| (pair[0].as_string(), pair[1].clone()))
+            .collect(),
+    ))
+}
+
+/**
+ * Usage: (map.get map key [default])
+ */
+fn map_get(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let map = eval_map(args.first(), fenv)?;
+    let key = eval_key(args.get(1), fenv)?;
+    match map.get(&key) {
+        Some(value) => Ok(value.clone()),
+        None => match args.get(2) {
+            Some(default) => fenv.eval(default),
+            None => Ok(Value::Nil),
+        },
+    }
+}
+
+/**
+ * Usage: (map.set map key value)
+ * Returns a new map, the original one is not modified.
+ */
+fn map_set(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mut map = eval_map(args.first(), fenv)?;
+    let key = eval_key(args.get(1), fenv)?;
+    let value = match args.get(2) {
+        Some(arg) => fenv.eval(arg)?,
+        None => anyhow::bail!("Expected a value"),
+    };
+    map.insert(key, value);
+    Ok(Value::Map(map))
+}
+
+fn map_remove(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mut map = eval_map(args.first(), fenv)?;
+    let key = eval_key(args.get(1), fenv)?;
+    map.remove(&key);
+    Ok(Value::Map(map))
+}
+
+fn map_has(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let map = eval_map(args.first(), fenv)?;
+    let key = eval_key(args.get(1), fenv)?;
+    Ok(Value::Bool(map.contains_key(&key)))
+}
+
+fn map_keys(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let map = eval_map(args.first(), fenv)?;
+    Ok(Value::List(map.into_keys().map(Value::String).collect()))
+}
+
+fn map_values(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let map = eval_map(args.first(), fenv)?;
+    Ok(Value::List(map.into_values().collect()))
+}
+
+fn map_len(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let map = eval_map(args.first(), fenv)?;
+    Ok(Value::Int(map.len() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_map_operations() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(r#"(def m (map "a" 1 "b" (+ 1 1)))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(map.get m "b")"#).unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(map.get m "c")"#).unwrap(),
+            Value::Nil
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(map.get m "c" 3)"#).unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(map.has m "a")"#).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            runtime
+                .eval_parsed(r#"(map.keys (map.set m "c" 3))"#)
+                .unwrap(),
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+                Value::String("c".to_string())
+            ])
+        );
+        assert_eq!(runtime.eval_string("(map.len m)").unwrap(), Value::Int(2));
+        assert_eq!(
+            runtime
+                .eval_string(r#"(map.len (map.remove m "a"))"#)
+                .unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            runtime.eval_string("(cast.string m)").unwrap(),
+            Value::String("{a: 1, b: 2}".to_string())
+        );
+        assert!(runtime.eval_string(r#"(map "a")"#).is_err());
+
+        let err = runtime.eval_string("(map.get)").unwrap_err();
+        assert_eq!(err.to_string(), "Expected a map");
+        let err = runtime.eval_string("(map.has m)").unwrap_err();
+        assert_eq!(err.to_string(), "Expected a key");
+        let err = runtime.eval_string(r#"(map.set m "c")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a value");
+    }
+}
@@ -1,20 +1,63 @@
-use rand::Rng;
+use rand::{
+    seq::{IndexedRandom, SliceRandom},
+    Rng,
+};
 
 use crate::{env::Environment, parser::Value};
 
+/// Bound as global variables, left out of `Runtime::list_globals`
+pub(crate) const CONSTANTS: [(&str, f64); 2] =
+    [("math.pi", std::f64::consts::PI), ("math.e", std::f64::consts::E)];
 
 pub(crate) fn register(env: &mut Environment) {
     env.add_native("rnd.int", random_int, false);
+    env.add_native("rnd.seed", random_seed, false);
+    env.add_native("rnd.float", random_float, false);
+    env.add_native("rnd.uniform", random_uniform, false);
+    env.add_native("rnd.gaussian", random_gaussian, false);
+    env.add_native("rnd.bool", random_bool, false);
+    env.add_native("rnd.choice", random_choice, false);
+    env.add_native("rnd.shuffle", random_shuffle, false);
+    env.add_native("rnd.sample", random_sample, false);
+
+    for (name, value) in CONSTANTS {
+        env.vars.set(&name.to_owned(), &Value::Float(value));
+    }
+
+    env.add_native("math.sqrt", |args, fenv| float_fn(args, fenv, f64::sqrt), false);
+    env.add_native("math.exp", |args, fenv| float_fn(args, fenv, f64::exp), false);
+    env.add_native("math.log", math_log, false);
+    env.add_native("math.log2", |args, fenv| float_fn(args, fenv, f64::log2), false);
+    env.add_native("math.log10", |args, fenv| float_fn(args, fenv, f64::log10), false);
+    env.add_native("math.sin", |args, fenv| float_fn(args, fenv, f64::sin), false);
+    env.add_native("math.cos", |args, fenv| float_fn(args, fenv, f64::cos), false);
+    env.add_native("math.tan", |args, fenv| float_fn(args, fenv, f64::tan), false);
+    env.add_native("math.asin", |args, fenv| float_fn(args, fenv, f64::asin), false);
+    env.add_native("math.acos", |args, fenv| float_fn(args, fenv, f64::acos), false);
+    env.add_native("math.atan", |args, fenv| float_fn(args, fenv, f64::atan), false);
+    env.add_native("math.atan2", math_atan2, false);
+    env.add_native("math.pow", math_pow, false);
+    env.add_native("math.abs", math_abs, false);
+    env.add_native("math.floor", |args, fenv| rounding_fn(args, fenv, f64::floor), false);
+    env.add_native("math.ceil", |args, fenv| rounding_fn(args, fenv, f64::ceil), false);
+    env.add_native("math.round", |args, fenv| rounding_fn(args, fenv, f64::round), false);
+    env.add_native("math.trunc", |args, fenv| rounding_fn(args, fenv, f64::trunc), false);
+    env.add_native("math.min", math_min, false);
+    env.add_native("math.max", math_max, false);
+    env.add_native("math.clamp", math_clamp, false);
+    env.add_native("math.gcd", math_gcd, false);
+    env.add_native("math.lcm", math_lcm, false);
+    env.add_native("math.is_nan", math_is_nan, false);
 }
 
 /**
  * Usage: (rnd.int [min] [max])
  * If no min is given, it will default to 0
  * If no max is given, it will default to 100
- * It will return a random integer between min and max
+ * It will return a random integer between min and max (both included)
  */
 fn random_int(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
-    let min = if let Some(min_val) = args.get(0) {
+    let min = if let Some(min_val) = args.first() {
         fenv.eval(min_val)?.as_int()
     } else {
         0
@@ -24,5 +67,516 @@ fn random_int(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
     } else {
         100
     };
-    Ok(Value::Int(rand::rng().random_range(min..=max)))
+    if min > max {
+        anyhow::bail!("Invalid range: {} is greater than {}", min, max);
+    }
+    Ok(Value::Int(fenv.rng().borrow_mut().random_range(min..=max)))
+}
+
+/**
+ * Usage: (rnd.seed n)
+ * Restarts the generator of the runtime, the same seed gives the same
+ * sequence of random values (for the same version of rssli).
+ */
+fn random_seed(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let seed = eval_int(args.first(), fenv)?;
+    fenv.seed_rng(seed as u64);
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (rnd.float)
+ * Returns a float between 0 (included) and 1 (excluded).
+ */
+fn random_float(_args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(Value::Float(fenv.rng().borrow_mut().random()))
+}
+
+/**
+ * Usage: (rnd.uniform min max)
+ * Returns a float between min (included) and max (excluded).
+ */
+fn random_uniform(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let min = eval_number(args.first(), fenv)?.as_float();
+    let max = eval_number(args.get(1), fenv)?.as_float();
+    if min >= max || !(max - min).is_finite() {
+        anyhow::bail!("Invalid range: {} to {}", min, max);
+    }
+    Ok(Value::Float(fenv.rng().borrow_mut().random_range(min..max)))
+}
+
+/**
+ * Usage: (rnd.gaussian [mean] [stddev])
+ * Returns a normally distributed float, by default with mean 0 and
+ * standard deviation 1.
+ */
+fn random_gaussian(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mean = match args.first() {
+        Some(_) => eval_number(args.first(), fenv)?.as_float(),
+        None => 0.0,
+    };
+    let stddev = match args.get(1) {
+        Some(_) => eval_number(args.get(1), fenv)?.as_float(),
+        None => 1.0,
+    };
+    if stddev < 0.0 || stddev.is_nan() {
+        anyhow::bail!("Standard deviation cannot be negative: {}", stddev);
+    }
+    // Box-Muller transform, 1 - u keeps the logarithm away from 0
+    let mut rng = fenv.rng().borrow_mut();
+    let u: f64 = rng.random();
+    let v: f64 = rng.random();
+    let z = (-2.0 * (1.0 - u).ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+    Ok(Value::Float(mean + stddev * z))
+}
+
+/**
+ * Usage: (rnd.bool [probability])
+ * Returns true with the given probability, by default 0.5.
+ */
+fn random_bool(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let probability = match args.first() {
+        Some(_) => eval_number(args.first(), fenv)?.as_float(),
+        None => 0.5,
+    };
+    if !(0.0..=1.0).contains(&probability) {
+        anyhow::bail!("Probability must be between 0 and 1, got {}", probability);
+    }
+    Ok(Value::Bool(fenv.rng().borrow_mut().random_bool(probability)))
+}
+
+/// Evaluates a list or a finite sequence, other values are one item lists
+/// (function calls unwrap single item lists)
+fn eval_items(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<Vec<Value>> {
+    match arg {
+        Some(arg) => match fenv.eval(arg)? {
+            Value::Seq(seq) => seq.collect(fenv),
+            value => Ok(value.as_list()),
+        },
+        None => anyhow::bail!("Expected a list"),
+    }
+}
+
+/**
+ * Usage: (rnd.choice list)
+ * Returns a random item of the list, nil when it is empty.
+ */
+fn random_choice(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let items = eval_items(args.first(), fenv)?;
+    let mut rng = fenv.rng().borrow_mut();
+    Ok(items.choose(&mut *rng).cloned().unwrap_or(Value::Nil))
+}
+
+/**
+ * Usage: (rnd.shuffle list)
+ * Returns the items in random order, the list itself is not changed.
+ */
+fn random_shuffle(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mut items = eval_items(args.first(), fenv)?;
+    items.shuffle(&mut *fenv.rng().borrow_mut());
+    Ok(Value::List(items))
+}
+
+/**
+ * Usage: (rnd.sample list count)
+ * Returns count different items of the list (without replacement), in
+ * random order.
+ */
+fn random_sample(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mut items = eval_items(args.first(), fenv)?;
+    let count = eval_int(args.get(1), fenv)?;
+    if count < 0 || count as usize > items.len() {
+        anyhow::bail!("Cannot sample {} items from a list of {}", count, items.len());
+    }
+    let count = count as usize;
+    items.partial_shuffle(&mut *fenv.rng().borrow_mut(), count);
+    items.truncate(count);
+    Ok(Value::List(items))
+}
+
+/// Evaluates a numeric argument, integers stay integers
+fn eval_number(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<Value> {
+    match arg {
+        Some(arg) => match fenv.eval(arg)? {
+            number @ (Value::Int(_) | Value::Float(_)) => Ok(number),
+            other => anyhow::bail!("Expected a number, got {}", other.type_name()),
+        },
+        None => anyhow::bail!("Expected a number"),
+    }
+}
+
+fn eval_int(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<i64> {
+    match eval_number(arg, fenv)? {
+        Value::Int(i) => Ok(i),
+        other => anyhow::bail!("Expected an integer, got {}", other.type_name()),
+    }
+}
+
+/// Evaluates all arguments as numbers. A single list argument is spread,
+/// so that (math.max xs) works like (math.max 1 2 3).
+fn eval_numbers(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Vec<Value>> {
+    let values = match fenv.eval_args(args)?.as_slice() {
+        [Value::List(items)] => items.clone(),
+        [Value::Seq(seq)] => seq.collect(fenv)?,
+        values => values.to_vec(),
+    };
+    for value in &values {
+        if !value.is_int() && !value.is_float() {
+            anyhow::bail!("Expected a number, got {}", value.type_name());
+        }
+    }
+    Ok(values)
+}
+
+/**
+ * Usage: (math.sqrt x), (math.exp x), (math.log2 x), (math.log10 x),
+ * (math.sin x), (math.cos x), (math.tan x), (math.asin x), (math.acos x),
+ * (math.atan x)
+ * Always return a float, angles are in radians. Results outside of the
+ * domain are NaN, e.g. (math.sqrt -1).
+ */
+fn float_fn(
+    args: &[Value],
+    fenv: &mut Environment,
+    func: fn(f64) -> f64,
+) -> anyhow::Result<Value> {
+    let x = eval_number(args.first(), fenv)?.as_float();
+    Ok(Value::Float(func(x)))
+}
+
+/**
+ * Usage: (math.log x [base])
+ * Natural logarithm, or the logarithm in the given base.
+ */
+fn math_log(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let x = eval_number(args.first(), fenv)?.as_float();
+    Ok(Value::Float(match args.get(1) {
+        Some(_) => x.log(eval_number(args.get(1), fenv)?.as_float()),
+        None => x.ln(),
+    }))
+}
+
+/**
+ * Usage: (math.atan2 y x)
+ * Angle of the point (x, y) in radians, between -pi and pi.
+ */
+fn math_atan2(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let y = eval_number(args.first(), fenv)?.as_float();
+    let x = eval_number(args.get(1), fenv)?.as_float();
+    Ok(Value::Float(y.atan2(x)))
+}
+
+/**
+ * Usage: (math.pow base exponent)
+ * Integer for an integer base and a non-negative integer exponent (an
+ * overflow is an error), otherwise a float.
+ */
+fn math_pow(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let base = eval_number(args.first(), fenv)?;
+    let exponent = eval_number(args.get(1), fenv)?;
+    Ok(match (base, exponent) {
+        (Value::Int(base), Value::Int(exponent)) if exponent >= 0 => {
+            let result = u32::try_from(exponent)
+                .ok()
+                .and_then(|exponent| base.checked_pow(exponent));
+            match result {
+                Some(result) => Value::Int(result),
+                None => anyhow::bail!("Integer overflow: {} to the power of {}", base, exponent),
+            }
+        }
+        (base, exponent) => Value::Float(base.as_float().powf(exponent.as_float())),
+    })
+}
+
+/**
+ * Usage: (math.abs x)
+ * Keeps the type of x.
+ */
+fn math_abs(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(match eval_number(args.first(), fenv)? {
+        Value::Int(i) => match i.checked_abs() {
+            Some(abs) => Value::Int(abs),
+            None => anyhow::bail!("Integer overflow: absolute value of {}", i),
+        },
+        x => Value::Float(x.as_float().abs()),
+    })
+}
+
+/**
+ * Usage: (math.floor x), (math.ceil x), (math.round x), (math.trunc x)
+ * Integers are returned unchanged, floats stay floats (use cast.int to get
+ * an integer). math.round rounds halves away from zero.
+ */
+fn rounding_fn(
+    args: &[Value],
+    fenv: &mut Environment,
+    func: fn(f64) -> f64,
+) -> anyhow::Result<Value> {
+    Ok(match eval_number(args.first(), fenv)? {
+        Value::Int(i) => Value::Int(i),
+        x => Value::Float(func(x.as_float())),
+    })
+}
+
+/// Smallest or largest of the numbers, a float when any of them is one
+fn extreme(args: &[Value], fenv: &mut Environment, largest: bool) -> anyhow::Result<Value> {
+    let values = eval_numbers(args, fenv)?;
+    if values.iter().any(Value::is_float) {
+        let floats = values.iter().map(Value::as_float);
+        let result = match largest {
+            true => floats.reduce(f64::max),
+            false => floats.reduce(f64::min),
+        };
+        return result
+            .map(Value::Float)
+            .ok_or_else(|| anyhow::anyhow!("Expected at least one number"));
+    }
+    let ints = values.iter().map(Value::as_int);
+    let result = match largest {
+        true => ints.max(),
+        false => ints.min(),
+    };
+    result
+        .map(Value::Int)
+        .ok_or_else(|| anyhow::anyhow!("Expected at least one number"))
+}
+
+/**
+ * Usage: (math.min x...), (math.min list)
+ * The result is a float when any of the numbers is a float.
+ */
+fn math_min(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    extreme(args, fenv, false)
+}
+
+/**
+ * Usage: (math.max x...), (math.max list)
+ * The result is a float when any of the numbers is a float.
+ */
+fn math_max(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    extreme(args, fenv, true)
+}
+
+/**
+ * Usage: (math.clamp x min max)
+ * Limits x to the range, a float when any of the arguments is a float.
+ */
+fn math_clamp(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let x = eval_number(args.first(), fenv)?;
+    let min = eval_number(args.get(1), fenv)?;
+    let max = eval_number(args.get(2), fenv)?;
+    Ok(match (x, min, max) {
+        (Value::Int(x), Value::Int(min), Value::Int(max)) => {
+            if min > max {
+                anyhow::bail!("Invalid range: {} is greater than {}", min, max);
+            }
+            Value::Int(x.clamp(min, max))
+        }
+        (x, min, max) => {
+            let (min, max) = (min.as_float(), max.as_float());
+            if min > max || min.is_nan() || max.is_nan() {
+                anyhow::bail!("Invalid range: {} to {}", min, max);
+            }
+            Value::Float(x.as_float().clamp(min, max))
+        }
+    })
+}
+
+fn gcd(a: i64, b: i64) -> u64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/**
+ * Usage: (math.gcd a b)
+ * Greatest common divisor of two integers, never negative.
+ */
+fn math_gcd(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let a = eval_int(args.first(), fenv)?;
+    let b = eval_int(args.get(1), fenv)?;
+    match i64::try_from(gcd(a, b)) {
+        Ok(gcd) => Ok(Value::Int(gcd)),
+        Err(_) => anyhow::bail!("Integer overflow: greatest common divisor of {} and {}", a, b),
+    }
+}
+
+/**
+ * Usage: (math.lcm a b)
+ * Least common multiple of two integers, never negative, 0 when either is 0.
+ */
+fn math_lcm(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let a = eval_int(args.first(), fenv)?;
+    let b = eval_int(args.get(1), fenv)?;
+    if a == 0 || b == 0 {
+        return Ok(Value::Int(0));
+    }
+    let lcm = (a.unsigned_abs() / gcd(a, b)).checked_mul(b.unsigned_abs());
+    match lcm.and_then(|lcm| i64::try_from(lcm).ok()) {
+        Some(lcm) => Ok(Value::Int(lcm)),
+        None => anyhow::bail!("Integer overflow: least common multiple of {} and {}", a, b),
+    }
+}
+
+/**
+ * Usage: (math.is_nan x)
+ * True for the float NaN, e.g. the result of (math.sqrt -1).
+ */
+fn math_is_nan(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(Value::Bool(match eval_number(args.first(), fenv)? {
+        Value::Float(f) => f.is_nan(),
+        _ => false,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn eval(runtime: &mut Runtime, prog: &str) -> Value {
+        runtime.eval_string(prog).unwrap()
+    }
+
+    #[test]
+    fn test_random_seed() {
+        let prog = "(list (rnd.int 0 1000) (rnd.float) (rnd.gaussian 10 2) (rnd.bool) \
+                    (rnd.uniform -1 1) (rnd.choice (list 1 2 3)) (rnd.shuffle (list 1 2 3 4)) \
+                    (rnd.sample (list 1 2 3 4) 2))";
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("(rnd.seed 42)").unwrap();
+        let first = runtime.eval_parsed(prog).unwrap();
+        runtime.eval_string("(rnd.seed 42)").unwrap();
+        assert_eq!(runtime.eval_parsed(prog).unwrap(), first);
+
+        let mut other = Runtime::builder().with_all_modules().seed(42).build().unwrap();
+        assert_eq!(other.eval_parsed(prog).unwrap(), first);
+        let snapshot = other.snapshot();
+        let next = other.eval_parsed(prog).unwrap();
+        assert_ne!(next, first);
+        other.restore(&snapshot);
+        assert_eq!(other.eval_parsed(prog).unwrap(), next);
+
+        // forks are seeded from their parent, siblings differ
+        let mut forks: Vec<_> = (0..2)
+            .map(|_| Runtime::builder().with_all_modules().seed(42).build().unwrap())
+            .map(|parent| [parent.fork(), parent.fork()])
+            .collect();
+        let draws: Vec<Vec<Value>> = forks
+            .iter_mut()
+            .map(|pair| pair.iter_mut().map(|f| f.eval_parsed(prog).unwrap()).collect())
+            .collect();
+        assert_eq!(draws[0], draws[1]);
+        assert_ne!(draws[0][0], draws[0][1]);
+        assert_ne!(draws[0][0], first);
+    }
+
+    #[test]
+    fn test_random_values() {
+        let mut runtime = Runtime::new_default();
+        runtime.seed(7);
+        for _ in 0..100 {
+            let int = eval(&mut runtime, "(rnd.int 5 7)").as_int();
+            assert!((5..=7).contains(&int));
+            let float = eval(&mut runtime, "(rnd.uniform 2 3)").as_float();
+            assert!((2.0..3.0).contains(&float));
+            let float = eval(&mut runtime, "(rnd.float)").as_float();
+            assert!((0.0..1.0).contains(&float));
+        }
+        assert_eq!(eval(&mut runtime, "(rnd.bool 0)"), Value::Bool(false));
+        assert_eq!(eval(&mut runtime, "(rnd.bool 1)"), Value::Bool(true));
+        assert_eq!(eval(&mut runtime, "(rnd.gaussian 5 0)"), Value::Float(5.0));
+        assert_eq!(eval(&mut runtime, "(rnd.choice (list))"), Value::Nil);
+
+        let Value::List(mut items) = runtime
+            .eval_parsed("(rnd.shuffle (seq.range 0 5))")
+            .unwrap()
+        else {
+            panic!("Expected a list");
+        };
+        items.sort_by_key(Value::as_int);
+        assert_eq!(items, (0..5).map(Value::Int).collect::<Vec<_>>());
+        let sample = runtime
+            .eval_parsed("(rnd.sample (list 1 2 3 4 5) 3)")
+            .unwrap()
+            .as_list();
+        assert_eq!(sample.len(), 3);
+        assert!(sample.iter().all(|v| (1..=5).contains(&v.as_int())));
+
+        let err = runtime.eval_string("(rnd.sample (list 1 2) 3)").unwrap_err();
+        assert_eq!(err.to_string(), "Cannot sample 3 items from a list of 2");
+        let err = runtime.eval_string("(rnd.int 5 1)").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid range: 5 is greater than 1");
+    }
+
+    #[test]
+    fn test_float_functions() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(eval(&mut runtime, "(math.sqrt 16)"), Value::Float(4.0));
+        assert_eq!(eval(&mut runtime, "(math.log 8 2)"), Value::Float(3.0));
+        assert_eq!(eval(&mut runtime, "(math.log10 1000)"), Value::Float(3.0));
+        assert_eq!(eval(&mut runtime, "(math.log math.e)"), Value::Float(1.0));
+        assert_eq!(eval(&mut runtime, "(math.cos 0)"), Value::Float(1.0));
+        assert_eq!(
+            eval(&mut runtime, "(math.atan2 1 1)"),
+            Value::Float(std::f64::consts::FRAC_PI_4)
+        );
+        assert_eq!(
+            eval(&mut runtime, "(* 2 (math.asin 1))"),
+            Value::Float(std::f64::consts::PI)
+        );
+        assert_eq!(eval(&mut runtime, "(math.is_nan (math.sqrt -1))"), Value::Bool(true));
+        assert_eq!(eval(&mut runtime, "(math.is_nan 1)"), Value::Bool(false));
+
+        let err = runtime.eval_string(r#"(math.sqrt "4")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a number, got string");
+    }
+
+    #[test]
+    fn test_promotion() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(eval(&mut runtime, "(math.pow 2 10)"), Value::Int(1024));
+        assert_eq!(eval(&mut runtime, "(math.pow 2 -1)"), Value::Float(0.5));
+        assert_eq!(eval(&mut runtime, "(math.pow 2.0 3)"), Value::Float(8.0));
+        let err = runtime.eval_string("(math.pow 10 20)").unwrap_err();
+        assert_eq!(err.to_string(), "Integer overflow: 10 to the power of 20");
+
+        assert_eq!(eval(&mut runtime, "(math.abs -3)"), Value::Int(3));
+        assert_eq!(eval(&mut runtime, "(math.abs -2.5)"), Value::Float(2.5));
+        assert_eq!(eval(&mut runtime, "(math.floor 7)"), Value::Int(7));
+        assert_eq!(eval(&mut runtime, "(math.floor -2.5)"), Value::Float(-3.0));
+        assert_eq!(eval(&mut runtime, "(math.ceil 2.1)"), Value::Float(3.0));
+        assert_eq!(eval(&mut runtime, "(math.round 2.5)"), Value::Float(3.0));
+        assert_eq!(eval(&mut runtime, "(math.trunc -2.7)"), Value::Float(-2.0));
+
+        assert_eq!(eval(&mut runtime, "(math.min 3 1 2)"), Value::Int(1));
+        assert_eq!(eval(&mut runtime, "(math.max 3 1 2.5)"), Value::Float(3.0));
+        assert_eq!(eval(&mut runtime, "(math.max (list 4 9 2))"), Value::Int(9));
+        assert_eq!(eval(&mut runtime, "(math.clamp 15 0 10)"), Value::Int(10));
+        assert_eq!(eval(&mut runtime, "(math.clamp -1 0.5 10)"), Value::Float(0.5));
+        let err = runtime.eval_string("(math.min)").unwrap_err();
+        assert_eq!(err.to_string(), "Expected at least one number");
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(eval(&mut runtime, "(math.gcd 12 -18)"), Value::Int(6));
+        assert_eq!(eval(&mut runtime, "(math.gcd 0 5)"), Value::Int(5));
+        assert_eq!(eval(&mut runtime, "(math.lcm 4 6)"), Value::Int(12));
+        assert_eq!(eval(&mut runtime, "(math.lcm -4 6)"), Value::Int(12));
+        assert_eq!(eval(&mut runtime, "(math.lcm 0 6)"), Value::Int(0));
+        runtime.set_global("min", Value::Int(i64::MIN));
+        let err = runtime.eval_string("(math.gcd min 0)").unwrap_err();
+        assert!(err.to_string().starts_with("Integer overflow"));
+        let err = runtime.eval_string("(math.gcd 1.5 3)").unwrap_err();
+        assert_eq!(err.to_string(), "Expected an integer, got float");
+        assert_eq!(
+            eval(&mut runtime, "(* 2 math.pi)"),
+            Value::Float(std::f64::consts::TAU)
+        );
+        assert_eq!(runtime.list_globals(), vec!["min".to_string()]);
+    }
 }
@@ -0,0 +1,204 @@
+use crate::{
+    corelib::core::make_closure,
+    env::Environment,
+    func::{FuncDef, FuncKind, FuncMetadata},
+    memo::Memo,
+    parser::Value,
+    sync::Shared,
+};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("memoize", bi_memoize, false);
+    env.add_native("fn-memo", bi_func_memo_def, true);
+    env.add_native("memo.stats", bi_memo_stats, false);
+    env.add_native("memo.clear", bi_memo_clear, false);
+}
+
+fn eval_memo(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Shared<Memo>> {
+    let func = match args.first() {
+        Some(arg) => fenv.eval(arg)?,
+        None => anyhow::bail!("Expected a memoized function"),
+    };
+    match func {
+        Value::Func(FuncKind::Memoized(memo)) => Ok(memo),
+        other => anyhow::bail!("Expected a memoized function, got {}", other.type_name()),
+    }
+}
+
+/**
+ * Usage: (memoize func [max-size])
+ * Returns a function caching the results of `func` by its arguments.
+ * With max-size, the least recently used results are evicted first.
+ */
+fn bi_memoize(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let evl = fenv.eval_args(args)?;
+    let Some(Value::Func(func)) = evl.first() else {
+        anyhow::bail!("memoize requires a function");
+    };
+    let capacity = evl.get(1).map(|size| size.as_int().max(0) as usize);
+    Ok(Value::Func(FuncKind::Memoized(Shared::new(Memo::new(func.clone(), capacity)))))
+}
+
+/**
+ * Usage: (fn-memo name (args) body)
+ * Same as `fn`, but the function is memoized, including its recursive calls.
+ */
+fn bi_func_memo_def(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (name, closure) = make_closure(args, fenv)?;
+    let kind = FuncKind::Memoized(Shared::new(Memo::new(closure, None)));
+
+    if name != "anonymous" {
+        let df = FuncDef {
+            metadata: FuncMetadata {
+                name: name.clone(),
+                same_env: false,
+            },
+            kind: kind.clone(),
+        };
+        fenv.funcs.set(&name, &df);
+    }
+    Ok(Value::Func(kind))
+}
+
+/**
+ * Usage: (memo.stats func)
+ * Returns (("hits" n) ("misses" n) ("size" n) ("capacity" n-or-nil))
+ */
+fn bi_memo_stats(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let stats = eval_memo(args, fenv)?.stats();
+    let pair = |name: &str, value: Value| Value::List(vec![Value::String(name.to_owned()), value]);
+    Ok(Value::List(vec![
+        pair("hits", Value::Int(stats.hits as i64)),
+        pair("misses", Value::Int(stats.misses as i64)),
+        pair("size", Value::Int(stats.size as i64)),
+        pair(
+            "capacity",
+            stats.capacity.map_or(Value::Nil, |capacity| Value::Int(capacity as i64)),
+        ),
+    ]))
+}
+
+fn bi_memo_clear(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    eval_memo(args, fenv)?.clear();
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memo::MemoStats, runtime::Runtime};
+
+    fn stats_of(runtime: &mut Runtime, name: &str) -> MemoStats {
+        match runtime.env().get_var_or_func(name).unwrap() {
+            Value::Func(FuncKind::Memoized(memo)) => memo.stats(),
+            other => panic!("{} is not memoized: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_fn_memo_caches_recursive_calls() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_string(
+                r#"
+            (
+                (fn-memo fib (x) (if (< x 3) 1 (+ (fib (- x 1)) (fib (- x 2)))))
+                (fib 40)
+            )
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, Value::Int(102334155));
+
+        let stats = stats_of(&mut runtime, "fib");
+        assert_eq!(stats.misses, 40);
+        assert_eq!(stats.size, 40);
+        assert_eq!(stats.hits, 37);
+    }
+
+    #[test]
+    fn test_memoize_closure_counts_calls() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_string(
+                r#"
+            (
+                (def calls 0)
+                (fn slow-square (x) ((def calls (+ calls 1)) (* x x)))
+                (def square (memoize slow-square))
+                (square 4)
+                (square 4)
+                (square 5)
+                (square 4)
+                calls
+            )
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, Value::Int(2));
+        let stats = stats_of(&mut runtime, "square");
+        assert_eq!(
+            stats,
+            MemoStats {
+                hits: 2,
+                misses: 2,
+                size: 2,
+                capacity: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_memoize_native() {
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("(def add (memoize +))").unwrap();
+        assert_eq!(runtime.eval_string("(add 1 2)").unwrap(), Value::Int(3));
+        assert_eq!(runtime.eval_string("(add 1 2)").unwrap(), Value::Int(3));
+        assert_eq!(runtime.eval_string("(add 1 2.5)").unwrap(), Value::Float(3.5));
+        assert_eq!(stats_of(&mut runtime, "add").hits, 1);
+
+        // list arguments reach the native as values, not as calls
+        runtime.eval_string("(def sum (memoize list.reduce))").unwrap();
+        assert_eq!(
+            runtime.eval_string("(sum (list 4 5) +)").unwrap(),
+            Value::Int(9)
+        );
+    }
+
+    #[test]
+    fn test_memoize_lru_eviction() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def inc (memoize (fn (x) (+ x 1)) 2))")
+            .unwrap();
+        runtime.eval_string("((inc 1) (inc 2) (inc 1) (inc 3))").unwrap();
+        // 2 was the least recently used entry
+        runtime.eval_string("(inc 1)").unwrap();
+        assert_eq!(stats_of(&mut runtime, "inc").hits, 2);
+        runtime.eval_string("(inc 2)").unwrap();
+        let stats = stats_of(&mut runtime, "inc");
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.size, 2);
+        assert_eq!(stats.capacity, Some(2));
+    }
+
+    #[test]
+    fn test_memo_stats_and_clear() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("((def id (memoize (fn (x) x))) (id 1) (id 1))")
+            .unwrap();
+        assert_eq!(
+            runtime.eval_parsed("(memo.stats id)").unwrap(),
+            Value::List(vec![
+                Value::List(vec![Value::String("hits".to_string()), Value::Int(1)]),
+                Value::List(vec![Value::String("misses".to_string()), Value::Int(1)]),
+                Value::List(vec![Value::String("size".to_string()), Value::Int(1)]),
+                Value::List(vec![Value::String("capacity".to_string()), Value::Nil]),
+            ])
+        );
+        runtime.eval_string("(memo.clear id)").unwrap();
+        assert_eq!(stats_of(&mut runtime, "id"), MemoStats::default());
+        assert!(runtime.eval_string("(memo.stats +)").is_err());
+    }
+}
@@ -3,25 +3,134 @@ use crate::env::Environment;
 mod assert;
 mod cast;
 mod core;
+mod csv;
+mod fs;
+mod gen;
 mod internal;
 mod io;
+mod json;
 mod list;
+mod map;
+mod object;
 mod ops;
+mod path;
 mod system;
 mod math;
+mod memo;
+mod seq;
 mod strings;
+mod time;
 
 pub use system::ProgramExitError;
 
+/// Builtin modules that can be installed individually (see `RuntimeBuilder`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Module {
+    /// fn, def, undef, if, while, for
+    Core,
+    /// print, io.*, log.* on the runtime streams, and io.open for files,
+    /// whose paths are checked by the filesystem sandbox like fs.*
+    Io,
+    /// cast.*
+    Cast,
+    /// arithmetic, comparison and logical operators
+    Ops,
+    /// head, last, list.*
+    List,
+    /// map, map.*
+    Map,
+    /// obj.type, obj.methods
+    Object,
+    /// internal.* introspection
+    Internal,
+    /// assert, assert.eq
+    Assert,
+    /// exit, import, sys.args, sys.script
+    System,
+    /// sys.exec, sys.spawn, env.* - processes and environment variables,
+    /// restricted by the sandbox
+    Process,
+    /// fs.* file access, restricted by the sandbox
+    Fs,
+    /// path.* manipulation of path strings
+    Path,
+    /// json.parse, json.stringify
+    Json,
+    /// csv.parse, csv.read, csv.stringify, csv.write
+    Csv,
+    /// math.*, rnd.* (seedable per runtime)
+    Math,
+    /// str.*, char.*
+    Strings,
+    /// time.now, time.sleep, time.format, time.measure, ...
+    Time,
+    /// seq.*
+    Seq,
+    /// gen, yield
+    Gen,
+    /// memoize, fn-memo, memo.*
+    Memo,
+}
+
+impl Module {
+    pub const ALL: [Module; 21] = [
+        Module::Core,
+        Module::Io,
+        Module::Cast,
+        Module::Ops,
+        Module::List,
+        Module::Map,
+        Module::Object,
+        Module::Internal,
+        Module::Assert,
+        Module::System,
+        Module::Process,
+        Module::Fs,
+        Module::Path,
+        Module::Json,
+        Module::Csv,
+        Module::Math,
+        Module::Strings,
+        Module::Time,
+        Module::Seq,
+        Module::Gen,
+        Module::Memo,
+    ];
+
+    pub(crate) fn register(self, env: &mut Environment) {
+        match self {
+            Module::Core => core::register(env),
+            Module::Io => io::register(env),
+            Module::Cast => cast::register(env),
+            Module::Ops => ops::register(env),
+            Module::List => list::register(env),
+            Module::Map => map::register(env),
+            Module::Object => object::register(env),
+            Module::Internal => internal::register(env),
+            Module::Assert => assert::register(env),
+            Module::System => system::register(env),
+            Module::Process => system::register_process(env),
+            Module::Fs => fs::register(env),
+            Module::Path => path::register(env),
+            Module::Json => json::register(env),
+            Module::Csv => csv::register(env),
+            Module::Math => math::register(env),
+            Module::Strings => strings::register(env),
+            Module::Time => time::register(env),
+            Module::Seq => seq::register(env),
+            Module::Gen => gen::register(env),
+            Module::Memo => memo::register(env),
+        }
+    }
+}
+
 pub(crate) fn register(env: &mut Environment) {
-    core::register(env);
-    io::register(env);
-    cast::register(env);
-    ops::register(env);
-    list::register(env);
-    internal::register(env);
-    assert::register(env);
-    system::register(env);
-    math::register(env);
-    strings::register(env);
+    for module in Module::ALL {
+        module.register(env);
+    }
+}
+
+/// Builtin constants are global variables, but not user definitions
+pub(crate) fn is_constant(name: &str) -> bool {
+    math::CONSTANTS.iter().any(|(constant, _)| *constant == name)
 }
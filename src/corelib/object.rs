@@ -0,0 +1,42 @@
+use crate::{env::Environment, object::Object, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("obj.type", bi_obj_type, false);
+    env.add_native("obj.methods", bi_obj_methods, false);
+}
+
+fn eval_object(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Object> {
+    let value = match args.first() {
+        Some(arg) => fenv.eval(arg)?,
+        None => anyhow::bail!("Expected an object"),
+    };
+    match value {
+        Value::Object(obj) => Ok(obj),
+        other => anyhow::bail!("Expected an object, got {}", other.type_name()),
+    }
+}
+
+/**
+ * Usage: (obj.type handle)
+ * Returns the type name of a host object.
+ */
+fn bi_obj_type(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(Value::String(
+        eval_object(args, fenv)?.type_name().to_owned(),
+    ))
+}
+
+/**
+ * Usage: (obj.methods handle)
+ * Returns the names of the methods callable as (obj.<name> handle args...)
+ */
+fn bi_obj_methods(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let obj = eval_object(args, fenv)?;
+    Ok(Value::List(
+        obj.object_type()
+            .method_names()
+            .into_iter()
+            .map(Value::String)
+            .collect(),
+    ))
+}
@@ -0,0 +1,290 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("path.join", path_join, false);
+    env.add_native("path.dirname", path_dirname, false);
+    env.add_native("path.basename", path_basename, false);
+    env.add_native("path.extension", path_extension, false);
+    env.add_native("path.with_extension", path_with_extension, false);
+    env.add_native("path.absolute", path_absolute, false);
+    env.add_native("path.normalize", path_normalize, false);
+    env.add_native("path.relative", path_relative, false);
+}
+
+fn eval_path(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<PathBuf> {
+    match arg {
+        Some(arg) => Ok(PathBuf::from(fenv.eval(arg)?.as_string())),
+        None => anyhow::bail!("Expected a path"),
+    }
+}
+
+fn path_value(path: &Path) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+/// Removes `.` and resolves `..` against the preceding component, without
+/// touching the filesystem. Leading `..` of relative paths are kept.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
+/**
+ * Usage: (path.join path part...)
+ * An absolute part replaces everything before it.
+ */
+fn path_join(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let mut path = PathBuf::new();
+    for part in fenv.eval_args(args)? {
+        path.push(part.as_string());
+    }
+    Ok(path_value(&path))
+}
+
+/**
+ * Usage: (path.dirname path)
+ * Returns "." for a bare file name and nil for a root.
+ */
+fn path_dirname(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    Ok(match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Value::String(".".to_owned()),
+        Some(parent) => path_value(parent),
+        None => Value::Nil,
+    })
+}
+
+/**
+ * Usage: (path.basename path)
+ * Returns the last component, nil when the path ends with "..".
+ */
+fn path_basename(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    Ok(path
+        .file_name()
+        .map_or(Value::Nil, |name| path_value(Path::new(name))))
+}
+
+/**
+ * Usage: (path.extension path)
+ * Returns the extension without the dot, nil when there is none.
+ */
+fn path_extension(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    Ok(path
+        .extension()
+        .map_or(Value::Nil, |ext| path_value(Path::new(ext))))
+}
+
+/**
+ * Usage: (path.with_extension path ext)
+ * Replaces or adds the extension, an empty one removes it.
+ */
+fn path_with_extension(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let ext = match args.get(1) {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected an extension"),
+    };
+    Ok(path_value(
+        &path.with_extension(ext.trim_start_matches('.')),
+    ))
+}
+
+/**
+ * Usage: (path.absolute path)
+ * Resolves a relative path against the directory of the current file (like
+ * import) and normalizes it. The path does not need to exist.
+ */
+fn path_absolute(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let absolute = fenv.resolve_path(&path.to_string_lossy())?;
+    Ok(path_value(&normalize(&absolute)))
+}
+
+/**
+ * Usage: (path.normalize path)
+ * Removes "." and resolves ".." without looking at the filesystem.
+ */
+fn path_normalize(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    Ok(path_value(&normalize(&path)))
+}
+
+/**
+ * Usage: (path.relative path [base])
+ * Returns the path relative to base, by default the directory of the
+ * current file. Relative arguments are resolved like path.absolute.
+ */
+fn path_relative(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let path = eval_path(args.first(), fenv)?;
+    let base = match args.get(1) {
+        Some(_) => eval_path(args.get(1), fenv)?,
+        None => fenv.base_dir()?,
+    };
+    let path = normalize(&fenv.resolve_path(&path.to_string_lossy())?);
+    let base = normalize(&fenv.resolve_path(&base.to_string_lossy())?);
+
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    while let (Some(a), Some(b)) = (path_parts.peek(), base_parts.peek()) {
+        if a != b {
+            break;
+        }
+        path_parts.next();
+        base_parts.next();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base_parts {
+        relative.push("..");
+    }
+    relative.extend(path_parts);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Ok(path_value(&relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn eval(runtime: &mut Runtime, prog: &str) -> Value {
+        runtime.eval_string(prog).unwrap()
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+
+    #[test]
+    fn test_path_parts() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(
+            eval(&mut runtime, r#"(path.join "a" "b" "c.txt")"#),
+            string("a/b/c.txt")
+        );
+        assert_eq!(eval(&mut runtime, r#"(path.join "a" "/b")"#), string("/b"));
+        assert_eq!(
+            eval(&mut runtime, r#"(path.dirname "a/b/c.txt")"#),
+            string("a/b")
+        );
+        assert_eq!(eval(&mut runtime, r#"(path.dirname "c.txt")"#), string("."));
+        assert_eq!(eval(&mut runtime, r#"(path.dirname "/")"#), Value::Nil);
+        assert_eq!(
+            eval(&mut runtime, r#"(path.basename "a/b/c.txt")"#),
+            string("c.txt")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.extension "a/c.tar.gz")"#),
+            string("gz")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.extension "a/Makefile")"#),
+            Value::Nil
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.with_extension "a/c.txt" ".md")"#),
+            string("a/c.md")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.with_extension "a/c.txt" "")"#),
+            string("a/c")
+        );
+    }
+
+    #[test]
+    fn test_normalize_and_relative() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(
+            eval(&mut runtime, r#"(path.normalize "a/./b/../c/")"#),
+            string("a/c")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.normalize "../a/../../b")"#),
+            string("../../b")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.normalize "/../a")"#),
+            string("/a")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.normalize "a/..")"#),
+            string(".")
+        );
+
+        assert_eq!(
+            eval(
+                &mut runtime,
+                r#"(path.relative "/srv/app/lib/a.lsp" "/srv/app/bin")"#
+            ),
+            string("../lib/a.lsp")
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.relative "/srv/app" "/srv/app")"#),
+            string(".")
+        );
+
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            eval(&mut runtime, r#"(path.absolute "x/../y.txt")"#),
+            path_value(&cwd.join("y.txt"))
+        );
+        assert_eq!(
+            eval(&mut runtime, r#"(path.relative "y.txt")"#),
+            string("y.txt")
+        );
+    }
+
+    #[test]
+    fn test_file_bindings() {
+        let dir = std::env::temp_dir().join(format!("rssli-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lib.lsp"), "(fn lib-file () __file__)").unwrap();
+        let script = dir.join("main.lsp");
+        std::fs::write(&script, "").unwrap();
+        let script = script.canonicalize().unwrap();
+
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("((def f __file__) (def d __dir__))").unwrap();
+        assert_eq!(runtime.get_global("f"), Some(Value::Nil));
+        assert_eq!(runtime.get_global("d"), Some(Value::Nil));
+
+        let result = runtime
+            .eval_file(
+                script.to_str().unwrap(),
+                r#"((import "lib.lsp") (lib-file))"#,
+            )
+            .unwrap();
+        // functions see the file they were defined in
+        assert_eq!(result, path_value(&script.with_file_name("lib.lsp")));
+        let result = runtime.eval_file(script.to_str().unwrap(), "(path.basename __file__)");
+        assert_eq!(result.unwrap(), string("main.lsp"));
+        let result = runtime
+            .eval_file(script.to_str().unwrap(), "(path.join __dir__)")
+            .unwrap();
+        assert_eq!(result, path_value(script.parent().unwrap()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    env::Environment,
+    parser::Value,
+    seq::{Seq, SeqKind},
+};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("seq.range", seq_range, false);
+    env.add_native("seq.map", seq_map, false);
+    env.add_native("seq.filter", seq_filter, false);
+    env.add_native("seq.take", seq_take, false);
+    env.add_native("seq.take-while", seq_take_while, false);
+    env.add_native("seq.collect", seq_collect, false);
+}
+
+/// Lists (and nil) are accepted anywhere a sequence is expected
+pub(crate) fn to_seq(value: Value) -> anyhow::Result<Seq> {
+    match value {
+        Value::Seq(seq) => Ok(seq),
+        Value::List(items) => Ok(Seq::new(SeqKind::List(items))),
+        Value::Nil => Ok(Seq::new(SeqKind::List(vec![]))),
+        other => anyhow::bail!("Expected a sequence or list, got {}", other.type_name()),
+    }
+}
+
+fn eval_seq_and_func(args: &[Value], fenv: &mut Environment) -> anyhow::Result<(Seq, Value)> {
+    let (Some(seq), Some(func)) = (args.first(), args.get(1)) else {
+        anyhow::bail!("Expected a sequence and a function");
+    };
+    let seq = to_seq(fenv.eval(seq)?)?;
+    let func = fenv.eval(func)?;
+    if !func.is_func() {
+        anyhow::bail!("Expected a function, got {}", func.type_name());
+    }
+    Ok((seq, func))
+}
+
+/**
+ * Usage: (seq.range [start] [end] [step])
+ * Without end (or with nil as end) the sequence is infinite.
+ * (seq.range) => 0, 1, 2, ...
+ * (seq.range 5) => 5, 6, 7, ...
+ * (seq.range 0 10 2) => 0, 2, 4, 6, 8
+ */
+fn seq_range(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let evl = fenv.eval_args(args)?;
+    let start = evl.first().map(Value::as_int).unwrap_or(0);
+    let end = evl.get(1).filter(|end| !end.is_nil()).map(Value::as_int);
+    let step = evl.get(2).map(Value::as_int).unwrap_or(1);
+    Ok(Value::Seq(Seq::range(start, end, step)?))
+}
+
+fn seq_map(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (seq, func) = eval_seq_and_func(args, fenv)?;
+    Ok(Value::Seq(Seq::new(SeqKind::Map(seq, func.as_func()))))
+}
+
+fn seq_filter(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (seq, func) = eval_seq_and_func(args, fenv)?;
+    Ok(Value::Seq(Seq::new(SeqKind::Filter(seq, func.as_func()))))
+}
+
+fn seq_take(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (Some(seq), Some(count)) = (args.first(), args.get(1)) else {
+        anyhow::bail!("seq.take requires a sequence and a count");
+    };
+    let seq = to_seq(fenv.eval(seq)?)?;
+    let count = fenv.eval(count)?.as_int().max(0) as usize;
+    Ok(Value::Seq(Seq::new(SeqKind::Take(seq, count))))
+}
+
+fn seq_take_while(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (seq, func) = eval_seq_and_func(args, fenv)?;
+    Ok(Value::Seq(Seq::new(SeqKind::TakeWhile(seq, func.as_func()))))
+}
+
+fn seq_collect(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let seq = match args.first() {
+        Some(arg) => to_seq(fenv.eval(arg)?)?,
+        None => anyhow::bail!("Expected a sequence"),
+    };
+    Ok(Value::List(seq.collect(fenv)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn ints(values: &[i64]) -> Value {
+        Value::List(values.iter().map(|x| Value::Int(*x)).collect())
+    }
+
+    #[test]
+    fn test_seq_range() {
+        let mut runtime = Runtime::new_default();
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (seq.range 0 5))").unwrap(),
+            ints(&[0, 1, 2, 3, 4])
+        );
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (seq.range 10 0 -3))").unwrap(),
+            ints(&[10, 7, 4, 1])
+        );
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (seq.take (seq.range 3) 3))").unwrap(),
+            ints(&[3, 4, 5])
+        );
+        assert!(runtime.eval_parsed("(seq.collect (seq.range))").is_err());
+        assert!(runtime.eval_parsed("(seq.range 0 10 0)").is_err());
+        let err = runtime.eval_parsed("(seq.collect)").unwrap_err();
+        assert_eq!(err.to_string(), "Expected a sequence");
+    }
+
+    #[test]
+    fn test_seq_map_filter_on_infinite_range() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_parsed(
+                r#"
+            (seq.collect
+                (seq.take
+                    (seq.filter (seq.map (seq.range) (fn (x) (* x x))) (fn (x) (== (% x 2) 1)))
+                    4))
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, ints(&[1, 9, 25, 49]));
+    }
+
+    #[test]
+    fn test_seq_take_while() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_parsed("(seq.collect (seq.take-while (seq.range 1) (fn (x) (< x 5))))")
+            .unwrap();
+        assert_eq!(result, ints(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_seq_accepts_lists() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_parsed("(seq.collect (seq.map (1 2 3) (fn (x) (+ x 1))))")
+            .unwrap();
+        assert_eq!(result, ints(&[2, 3, 4]));
+    }
+
+    #[test]
+    fn test_seq_is_reiterable() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string("(def s (seq.map (seq.range 0 3) (fn (x) (* x 10))))")
+            .unwrap();
+        assert_eq!(runtime.eval_parsed("(seq.collect s)").unwrap(), ints(&[0, 10, 20]));
+        assert_eq!(runtime.eval_parsed("(seq.collect s)").unwrap(), ints(&[0, 10, 20]));
+    }
+
+    #[test]
+    fn test_for_iterates_lazily() {
+        let mut runtime = Runtime::new_default();
+        let result = runtime
+            .eval_string(
+                r#"
+            (
+                (def total 0)
+                (for i (seq.take (seq.range 0 100000000000) 5) (def total (+ total i)))
+                total
+            )
+            "#,
+            )
+            .unwrap();
+        assert_eq!(result, Value::Int(10));
+    }
+}
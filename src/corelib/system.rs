@@ -1,5 +1,19 @@
-use crate::{env::Environment, parser::Value};
-use std::fmt;
+use crate::{
+    corelib::io::read_line,
+    env::Environment,
+    object::{Object, ObjectType},
+    parser::Value,
+    seq::{Seq, SeqSource},
+    sync::{Lock, Shared},
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    thread::JoinHandle,
+};
 
 /// Custom error type for program exit
 #[derive(Debug, Clone)]
@@ -18,10 +32,23 @@ impl std::error::Error for ProgramExitError {}
 pub(crate) fn register(env: &mut Environment) {
     env.add_native("exit", exit_with_code, false);
     env.add_native("import", bi_import, true);
+    env.add_native("sys.args", sys_args, false);
+    env.add_native("sys.script", sys_script, false);
+}
+
+/// Running programs and reading environment variables, a separate module so
+/// that `exit` and `import` do not grant them
+pub(crate) fn register_process(env: &mut Environment) {
+    env.add_native("sys.exec", sys_exec, false);
+    env.add_native("sys.spawn", sys_spawn, false);
+    env.add_native("env.get", env_get, false);
+    env.add_native("env.set", env_set, false);
+    env.add_native("env.vars", env_vars, false);
+    env.register_object_type("process");
 }
 
 fn exit_with_code(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
-    let code = if let Some(code_arg) = args.get(0) {
+    let code = if let Some(code_arg) = args.first() {
         fenv.eval(code_arg)?.as_int() as i32
     } else {
         0
@@ -39,3 +66,587 @@ fn bi_import(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
 
     fenv.import_file(&path_str)
 }
+
+/**
+ * Usage: (sys.args)
+ * Returns the command-line arguments of the script as a list of strings.
+ */
+fn sys_args(_args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(Value::List(
+        fenv.args().iter().cloned().map(Value::String).collect(),
+    ))
+}
+
+/**
+ * Usage: (sys.script)
+ * Returns the path of the script given on the command line, nil in the REPL
+ * and for eval. Unlike __file__ it does not change inside imported files.
+ */
+fn sys_script(_args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(fenv
+        .script()
+        .map_or(Value::Nil, |script| Value::String(script.to_owned())))
+}
+
+/// Options of `sys.exec` and `sys.spawn`
+struct ExecOptions {
+    stdin: Option<String>,
+    cwd: Option<String>,
+    env: BTreeMap<String, Value>,
+}
+
+/// Evaluates the argv list and options and prepares the command, after
+/// checking that the sandbox allows running it
+fn eval_command(
+    args: &[Value],
+    fenv: &mut Environment,
+    allow_stdin: bool,
+) -> anyhow::Result<(Command, ExecOptions)> {
+    let argv = match args.first() {
+        // a list with a single item arrives unwrapped
+        Some(arg) => match fenv.eval(arg)? {
+            Value::List(items) => items.iter().map(Value::as_string).collect::<Vec<_>>(),
+            Value::String(program) => vec![program],
+            other => anyhow::bail!("Expected an argv list, got {}", other.type_name()),
+        },
+        None => anyhow::bail!("Expected an argv list"),
+    };
+    let Some((program, program_args)) = argv.split_first() else {
+        anyhow::bail!("Expected a program to run");
+    };
+    fenv.sandbox().check_exec(program)?;
+
+    let mut options = ExecOptions {
+        stdin: None,
+        cwd: None,
+        env: BTreeMap::new(),
+    };
+    let entries = match args.get(1) {
+        Some(arg) => match fenv.eval(arg)? {
+            Value::Map(entries) => entries,
+            Value::Nil => BTreeMap::new(),
+            other => anyhow::bail!("Expected an options map, got {}", other.type_name()),
+        },
+        None => BTreeMap::new(),
+    };
+    for (key, value) in entries {
+        match (key.as_str(), value) {
+            ("stdin", value) if allow_stdin => options.stdin = Some(value.as_string()),
+            ("cwd", value) => options.cwd = Some(value.as_string()),
+            ("env", Value::Map(vars)) => options.env = vars,
+            ("env", other) => anyhow::bail!("Expected a map for env, got {}", other.type_name()),
+            (key, _) => anyhow::bail!("Unknown option: {}", key),
+        }
+    }
+
+    let mut command = Command::new(program);
+    command.args(program_args);
+    fenv.apply_env_vars(&mut command);
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(fenv.checked_path(cwd)?);
+    }
+    for (name, value) in &options.env {
+        match value {
+            Value::Nil => command.env_remove(name),
+            value => command.env(name, value.as_string()),
+        };
+    }
+    Ok((command, options))
+}
+
+fn exit_code(status: ExitStatus) -> Value {
+    status.code().map_or(Value::Nil, |code| Value::Int(code as i64))
+}
+
+/**
+ * Usage: (sys.exec argv [options])
+ * Runs a program without a shell and waits for it, e.g.
+ * (sys.exec ("git" "status" "--short") (map "cwd" "repo")).
+ * Returns a map with "code" (nil when killed by a signal), "stdout" and
+ * "stderr". Options: "stdin" is written to the input of the program, "cwd"
+ * is resolved like import, "env" adds variables (nil removes one) to the
+ * ones of the runtime, see env.set.
+ * Denied by the sandbox when file access is restricted, unless allowed
+ * with allow_exec.
+ */
+fn sys_exec(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (mut command, options) = eval_command(args, fenv, true)?;
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Cannot run {}: {}", program, e))?;
+
+    // written from a thread, the program may fill its output pipes before
+    // reading all of its input
+    let stdin = child.stdin.take();
+    let input = options.stdin.unwrap_or_default();
+    let writer = std::thread::spawn(move || match stdin {
+        Some(mut stdin) => stdin.write_all(input.as_bytes()),
+        None => Ok(()),
+    });
+    let output = child.wait_with_output()?;
+    match writer.join() {
+        Ok(Err(e)) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+        _ => {}
+    }
+
+    Ok(Value::Map(BTreeMap::from([
+        ("code".to_owned(), exit_code(output.status)),
+        (
+            "stdout".to_owned(),
+            Value::String(String::from_utf8_lossy(&output.stdout).into_owned()),
+        ),
+        (
+            "stderr".to_owned(),
+            Value::String(String::from_utf8_lossy(&output.stderr).into_owned()),
+        ),
+    ])))
+}
+
+/**
+ * Usage: (sys.spawn argv [options])
+ * Starts a program and returns a process object to stream its output,
+ * e.g. (for line (process.lines p) (print line)). Takes the options of
+ * sys.exec except "stdin", the input is written with the write and
+ * write_line methods. Stderr is copied to the error stream of the runtime,
+ * line by line whenever the output is read or input written, the rest by
+ * wait. Other methods: read_line, read_all, close_stdin, wait (returns the
+ * exit code, discarding unread output), kill and pid.
+ */
+fn sys_spawn(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let (mut command, _) = eval_command(args, fenv, false)?;
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Cannot run {}: {}", program, e))?;
+
+    // collected from a thread so that the program never blocks on a full pipe
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let stderr_reader = child.stderr.take().map(|mut pipe| {
+        let stderr = stderr.clone();
+        std::thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(len @ 1..) = pipe.read(&mut chunk) {
+                let mut stderr = stderr.lock().unwrap_or_else(PoisonError::into_inner);
+                stderr.extend_from_slice(&chunk[..len]);
+            }
+        })
+    });
+    let process = Process {
+        stdout: child.stdout.take().map(BufReader::new),
+        stdin: child.stdin.take(),
+        stderr,
+        stderr_reader,
+        child,
+    };
+    let handle = ProcessHandle {
+        program,
+        process: Shared::new(Lock::new(process)),
+    };
+    Ok(Value::Object(Object::new(process_type(), handle)))
+}
+
+struct Process {
+    child: Child,
+    /// `None` once read to the end by `wait`
+    stdout: Option<BufReader<ChildStdout>>,
+    /// `None` once closed
+    stdin: Option<ChildStdin>,
+    /// Output to stderr not yet copied to the runtime streams
+    stderr: Arc<Mutex<Vec<u8>>>,
+    /// `None` once the program closed its stderr and it was all collected
+    stderr_reader: Option<JoinHandle<()>>,
+}
+
+/// Data of `process` objects
+#[derive(Clone)]
+struct ProcessHandle {
+    program: String,
+    process: Shared<Lock<Process>>,
+}
+
+impl ProcessHandle {
+    fn with_stdout<R, F>(&self, func: F) -> anyhow::Result<R>
+    where
+        F: FnOnce(&mut BufReader<ChildStdout>) -> io::Result<R>,
+    {
+        match self.process.borrow_mut().stdout.as_mut() {
+            Some(stdout) => Ok(func(stdout)?),
+            None => anyhow::bail!("Output of {} was already consumed", self.program),
+        }
+    }
+
+    /// Copies the complete lines the program wrote to stderr so far to the
+    /// error stream of the runtime, or everything once it has exited
+    fn forward_stderr(&self, fenv: &Environment, exited: bool) -> anyhow::Result<()> {
+        let bytes = {
+            let mut process = self.process.borrow_mut();
+            if exited {
+                if let Some(reader) = process.stderr_reader.take() {
+                    let _ = reader.join();
+                }
+            }
+            let mut stderr = process.stderr.lock().unwrap_or_else(PoisonError::into_inner);
+            let end = match exited {
+                true => stderr.len(),
+                false => stderr.iter().rposition(|b| *b == b'\n').map_or(0, |pos| pos + 1),
+            };
+            stderr.drain(..end).collect::<Vec<_>>()
+        };
+        if !bytes.is_empty() {
+            fenv.streams().write_err(&String::from_utf8_lossy(&bytes))?;
+        }
+        Ok(())
+    }
+
+    fn read_line(&self, fenv: &Environment) -> anyhow::Result<Value> {
+        let line = self.with_stdout(|stdout| read_line(|buf| stdout.read_line(buf)))?;
+        self.forward_stderr(fenv, false)?;
+        Ok(line.map_or(Value::Nil, Value::String))
+    }
+
+    fn write(&self, text: &str, fenv: &Environment) -> anyhow::Result<Value> {
+        match self.process.borrow_mut().stdin.as_mut() {
+            Some(stdin) => stdin.write_all(text.as_bytes())?,
+            None => anyhow::bail!("Input of {} is closed", self.program),
+        }
+        self.forward_stderr(fenv, false)?;
+        Ok(Value::Nil)
+    }
+
+    /// Closes the input and reads the rest of the output, so that the
+    /// program does not block on a full pipe, then waits for it to exit
+    fn wait(&self, fenv: &Environment) -> anyhow::Result<Value> {
+        let status = {
+            let mut process = self.process.borrow_mut();
+            process.stdin = None;
+            if let Some(mut stdout) = process.stdout.take() {
+                io::copy(&mut stdout, &mut io::sink())?;
+            }
+            process.child.wait()?
+        };
+        self.forward_stderr(fenv, true)?;
+        Ok(exit_code(status))
+    }
+}
+
+struct ProcessLines(ProcessHandle);
+
+impl SeqSource for ProcessLines {
+    fn next(&self, env: &mut Environment) -> anyhow::Result<Option<Value>> {
+        match self.0.read_line(env)? {
+            Value::Nil => Ok(None),
+            line => Ok(Some(line)),
+        }
+    }
+}
+
+fn process_type() -> Shared<ObjectType> {
+    Shared::new(
+        ObjectType::new("process")
+            .with_display(|p: &ProcessHandle| {
+                format!("<process {} {}>", p.program, p.process.borrow().child.id())
+            })
+            .with_method("read_line", |p: &ProcessHandle, _args, fenv| p.read_line(fenv))
+            .with_method("read_all", |p: &ProcessHandle, _args, fenv| {
+                let mut buffer = String::new();
+                p.with_stdout(|stdout| stdout.read_to_string(&mut buffer))?;
+                p.forward_stderr(fenv, false)?;
+                Ok(Value::String(buffer))
+            })
+            .with_method("lines", |p: &ProcessHandle, _args, _fenv| {
+                Ok(Value::Seq(Seq::from_source(ProcessLines(p.clone()))))
+            })
+            .with_method("write", |p: &ProcessHandle, args, fenv| {
+                let text: String = args.iter().map(Value::as_string).collect();
+                p.write(&text, fenv)
+            })
+            .with_method("write_line", |p: &ProcessHandle, args, fenv| {
+                let parts: Vec<_> = args.iter().map(Value::as_string).collect();
+                p.write(&format!("{}\n", parts.join(" ")), fenv)
+            })
+            .with_method("close_stdin", |p: &ProcessHandle, _args, _fenv| {
+                p.process.borrow_mut().stdin = None;
+                Ok(Value::Nil)
+            })
+            .with_method("wait", |p: &ProcessHandle, _args, fenv| p.wait(fenv))
+            .with_method("kill", |p: &ProcessHandle, _args, _fenv| {
+                p.process.borrow_mut().child.kill()?;
+                Ok(Value::Nil)
+            })
+            .with_method("pid", |p: &ProcessHandle, _args, _fenv| {
+                Ok(Value::Int(p.process.borrow().child.id() as i64))
+            }),
+    )
+}
+
+fn eval_name(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<String> {
+    let name = match arg {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a variable name"),
+    };
+    fenv.sandbox().check_env(&name)?;
+    Ok(name)
+}
+
+/**
+ * Usage: (env.get name [default])
+ * Returns the environment variable, or default (nil) when it is not set.
+ */
+fn env_get(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let name = eval_name(args.first(), fenv)?;
+    match fenv.env_var(&name) {
+        Some(value) => Ok(Value::String(value)),
+        None => match args.get(1) {
+            Some(default) => fenv.eval(default),
+            None => Ok(Value::Nil),
+        },
+    }
+}
+
+/**
+ * Usage: (env.set name value)
+ * Sets the environment variable for this runtime, seen by env.get and the
+ * programs it runs afterwards. A nil value removes it. The environment of
+ * the interpreter process and of other runtimes is not changed.
+ */
+fn env_set(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let name = eval_name(args.first(), fenv)?;
+    if name.is_empty() || name.contains(['=', '\0']) {
+        anyhow::bail!("Invalid environment variable name: {:?}", name);
+    }
+    match args.get(1) {
+        Some(arg) => match fenv.eval(arg)? {
+            Value::Nil => fenv.set_env_var(&name, None),
+            value => {
+                let value = value.as_string();
+                if value.contains('\0') {
+                    anyhow::bail!("Environment variable {} cannot contain NUL", name);
+                }
+                fenv.set_env_var(&name, Some(value))
+            }
+        },
+        None => anyhow::bail!("Expected a value"),
+    }
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (env.vars)
+ * Returns all environment variables as a map, including the ones set with
+ * env.set.
+ */
+fn env_vars(_args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    fenv.sandbox().check_env("*")?;
+    Ok(Value::Map(
+        fenv.env_vars()
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+
+    #[test]
+    fn test_exec() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("script", string("cat; echo err >&2; exit 3"));
+        runtime
+            .eval_string(r#"(def result (sys.exec ("sh" "-c" script) (map "stdin" "in")))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.get_global("result"),
+            Some(Value::Map(BTreeMap::from([
+                ("code".to_owned(), Value::Int(3)),
+                ("stdout".to_owned(), string("in")),
+                ("stderr".to_owned(), string("err\n")),
+            ])))
+        );
+
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        runtime.set_global("dir", string(dir.to_str().unwrap()));
+        runtime
+            .eval_string(r#"(def options (map "cwd" dir "env" (map "X" 1)))"#)
+            .unwrap();
+        runtime
+            .eval_string(r#"(def result (sys.exec ("sh" "-c" "pwd; echo $X") options))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(map.get result "stdout")"#).unwrap(),
+            string(&format!("{}\n1\n", dir.display()))
+        );
+
+        let err = runtime
+            .eval_string(r#"(sys.exec ("rssli-no-such-program"))"#)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Cannot run rssli-no-such-program"));
+    }
+
+    #[test]
+    fn test_spawn() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("script", string("echo one; read x; echo got $x; exit 2"));
+        runtime
+            .eval_string(r#"(def p (sys.spawn ("sh" "-c" script)))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string("(process.read_line p)").unwrap(),
+            string("one")
+        );
+        runtime.eval_string(r#"(process.write_line p "two")"#).unwrap();
+        assert_eq!(
+            runtime.eval_parsed("(seq.collect (process.lines p))").unwrap(),
+            string("got two")
+        );
+        assert_eq!(
+            runtime.eval_string("(process.wait p)").unwrap(),
+            Value::Int(2)
+        );
+
+        let errors = runtime.capture_errors();
+        runtime.set_global("script", string("echo warn >&2; echo out; echo done >&2"));
+        runtime
+            .eval_string(r#"(def p (sys.spawn ("sh" "-c" script)))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string("(process.read_line p)").unwrap(),
+            string("out")
+        );
+        assert_eq!(
+            runtime.eval_string("(process.wait p)").unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(errors.contents(), "warn\ndone\n");
+    }
+
+    #[test]
+    fn test_env_and_args() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(r#"(env.set "RSSLI_TEST_VAR" "value")"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(env.get "RSSLI_TEST_VAR")"#).unwrap(),
+            string("value")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(map.get (env.vars) "RSSLI_TEST_VAR")"#)
+                .unwrap(),
+            string("value")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(map.get (sys.exec ("sh" "-c" "echo $RSSLI_TEST_VAR")) "stdout")"#)
+                .unwrap(),
+            string("value\n")
+        );
+        // only this runtime and its forks see the variable
+        assert!(std::env::var("RSSLI_TEST_VAR").is_err());
+        let mut fork = runtime.fork();
+        assert_eq!(
+            fork.eval_string(r#"(env.get "RSSLI_TEST_VAR")"#).unwrap(),
+            string("value")
+        );
+        let mut other = Runtime::new_default();
+        assert_eq!(
+            other.eval_string(r#"(env.get "RSSLI_TEST_VAR")"#).unwrap(),
+            Value::Nil
+        );
+
+        runtime
+            .eval_string(r#"(env.set "RSSLI_TEST_VAR" nil)"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(env.get "RSSLI_TEST_VAR" "none")"#).unwrap(),
+            string("none")
+        );
+        assert_eq!(
+            fork.eval_string(r#"(env.get "RSSLI_TEST_VAR")"#).unwrap(),
+            string("value")
+        );
+        runtime.eval_string(r#"(env.set "HOME" nil)"#).unwrap();
+        assert_eq!(
+            runtime
+                .eval_string(r#"(map.get (sys.exec ("sh" "-c" "echo ${HOME-unset}")) "stdout")"#)
+                .unwrap(),
+            string("unset\n")
+        );
+        assert_eq!(
+            runtime.eval_string(r#"(map.has (env.vars) "HOME")"#).unwrap(),
+            Value::Bool(false)
+        );
+
+        assert_eq!(runtime.eval_parsed("(sys.args)").unwrap(), Value::List(vec![]));
+        runtime.set_args(["a", "b"]);
+        assert_eq!(
+            runtime.eval_parsed("(sys.args)").unwrap(),
+            Value::List(vec![string("a"), string("b")])
+        );
+        assert_eq!(runtime.eval_string("(sys.script)").unwrap(), Value::Nil);
+        runtime.set_script("bin/run.lsp");
+        assert_eq!(
+            runtime.eval_string("((fn f () (sys.script)) (f))").unwrap(),
+            string("bin/run.lsp")
+        );
+    }
+
+    #[test]
+    fn test_capabilities_denied() {
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .deny_exec()
+            .deny_env()
+            .build()
+            .unwrap();
+        let err = runtime.eval_string(r#"(sys.exec ("ls"))"#).unwrap_err();
+        assert_eq!(err.to_string(), "Process execution is denied: ls");
+        let err = runtime.eval_string(r#"(sys.spawn ("ls"))"#).unwrap_err();
+        assert_eq!(err.to_string(), "Process execution is denied: ls");
+        let err = runtime.eval_string(r#"(env.get "HOME")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Environment access is denied: HOME");
+        let err = runtime.eval_string("(env.vars)").unwrap_err();
+        assert_eq!(err.to_string(), "Environment access is denied: *");
+    }
+
+    #[test]
+    fn test_restricted_fs_denies_capabilities() {
+        let dir = std::env::temp_dir();
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .allow_dir(&dir)
+            .build()
+            .unwrap();
+        let err = runtime.eval_string(r#"(sys.exec ("true"))"#).unwrap_err();
+        assert_eq!(err.to_string(), "Process execution is denied: true");
+        let err = runtime.eval_string(r#"(env.get "HOME")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Environment access is denied: HOME");
+
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .deny_fs()
+            .allow_exec()
+            .allow_env()
+            .build()
+            .unwrap();
+        runtime.eval_string(r#"(env.set "RSSLI_MODE" "test")"#).unwrap();
+        let result = runtime
+            .eval_string(r#"(map.get (sys.exec ("sh" "-c" "echo $RSSLI_MODE")) "stdout")"#)
+            .unwrap();
+        assert_eq!(result, string("test\n"));
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc,
+};
+
+use crate::{env::Environment, parser::Value};
+
+pub(crate) fn register(env: &mut Environment) {
+    env.add_native("time.now", time_now, false);
+    env.add_native("time.monotonic", time_monotonic, false);
+    env.add_native("time.sleep", time_sleep, false);
+    env.add_native("time.format", time_format, false);
+    env.add_native("time.parse", time_parse, false);
+    env.add_native("time.measure", time_measure, true);
+}
+
+fn millis(duration: Duration) -> Value {
+    Value::Float(duration.as_secs_f64() * 1000.0)
+}
+
+fn eval_timestamp(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<DateTime<Utc>> {
+    let millis = match arg {
+        Some(arg) => fenv.eval(arg)?.as_int(),
+        None => anyhow::bail!("Expected a timestamp in milliseconds"),
+    };
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| anyhow::anyhow!("Timestamp out of range: {}", millis))
+}
+
+/// Evaluates an optional strftime pattern, rejecting invalid ones (chrono
+/// only reports them when formatting)
+fn eval_pattern(arg: Option<&Value>, fenv: &mut Environment) -> anyhow::Result<Option<String>> {
+    let Some(arg) = arg else {
+        return Ok(None);
+    };
+    let pattern = fenv.eval(arg)?.as_string();
+    if StrftimeItems::new(&pattern).any(|item| matches!(item, Item::Error)) {
+        anyhow::bail!("Invalid time pattern: {}", pattern);
+    }
+    Ok(Some(pattern))
+}
+
+/**
+ * Usage: (time.now)
+ * Returns the current time as milliseconds since the Unix epoch.
+ */
+fn time_now(_args: &[Value], _fenv: &mut Environment) -> anyhow::Result<Value> {
+    Ok(Value::Int(Utc::now().timestamp_millis()))
+}
+
+/**
+ * Usage: (time.monotonic)
+ * Returns milliseconds (a float) of a clock that never goes back, counted
+ * from its first use. Only differences between two readings are meaningful.
+ */
+fn time_monotonic(_args: &[Value], _fenv: &mut Environment) -> anyhow::Result<Value> {
+    static START: OnceLock<Instant> = OnceLock::new();
+    Ok(millis(START.get_or_init(Instant::now).elapsed()))
+}
+
+/**
+ * Usage: (time.sleep millis)
+ * Blocks the runtime for the given number of milliseconds.
+ */
+fn time_sleep(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let millis = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_float(),
+        None => anyhow::bail!("Expected the number of milliseconds"),
+    };
+    let Ok(duration) = Duration::try_from_secs_f64(millis / 1000.0) else {
+        anyhow::bail!("Cannot sleep for {} milliseconds", millis);
+    };
+    std::thread::sleep(duration);
+    Ok(Value::Nil)
+}
+
+/**
+ * Usage: (time.format millis [pattern])
+ * Formats a timestamp in UTC with a strftime pattern, e.g. "%Y-%m-%d %H:%M".
+ * Without a pattern the result is RFC 3339, e.g. "2024-05-01T12:30:00.000Z".
+ */
+fn time_format(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let time = eval_timestamp(args.first(), fenv)?;
+    Ok(Value::String(match eval_pattern(args.get(1), fenv)? {
+        Some(pattern) => time.format(&pattern).to_string(),
+        None => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+    }))
+}
+
+/**
+ * Usage: (time.parse text [pattern])
+ * Parses a time into milliseconds since the Unix epoch. Without a pattern
+ * the text is RFC 3339. Times without an offset (%z) are in UTC, a pattern
+ * without a time of day gives midnight.
+ */
+fn time_parse(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let text = match args.first() {
+        Some(arg) => fenv.eval(arg)?.as_string(),
+        None => anyhow::bail!("Expected a time string"),
+    };
+    let time = match eval_pattern(args.get(1), fenv)? {
+        None => DateTime::parse_from_rfc3339(&text)
+            .map(|time| time.to_utc())
+            .map_err(|e| anyhow::anyhow!("Cannot parse time {:?}: {}", text, e))?,
+        Some(pattern) => DateTime::parse_from_str(&text, &pattern)
+            .map(|time| time.to_utc())
+            .or_else(|_| NaiveDateTime::parse_from_str(&text, &pattern).map(|t| t.and_utc()))
+            .or_else(|e| {
+                NaiveDate::parse_from_str(&text, &pattern)
+                    .map(|date| date.and_time(Default::default()).and_utc())
+                    .map_err(|_| e)
+            })
+            .map_err(|e| {
+                anyhow::anyhow!("Cannot parse time {:?} as {:?}: {}", text, pattern, e)
+            })?,
+    };
+    Ok(Value::Int(time.timestamp_millis()))
+}
+
+/**
+ * Usage: (time.measure expr...)
+ * Evaluates the expressions and returns how long it took in milliseconds
+ * (a float). Definitions made by the expressions stay visible afterwards.
+ */
+fn time_measure(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value> {
+    let start = Instant::now();
+    for arg in args {
+        fenv.eval(arg)?;
+    }
+    Ok(millis(start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_owned())
+    }
+
+    #[test]
+    fn test_format_and_parse() {
+        let mut runtime = Runtime::new_default();
+        // 2024-05-01 12:30:15.250 UTC
+        runtime.set_global("t", Value::Int(1714566615250));
+        assert_eq!(
+            runtime.eval_string("(time.format t)").unwrap(),
+            string("2024-05-01T12:30:15.250Z")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(time.format t "%d.%m.%Y %H:%M")"#)
+                .unwrap(),
+            string("01.05.2024 12:30")
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(time.parse "2024-05-01T14:30:15.250+02:00")"#)
+                .unwrap(),
+            Value::Int(1714566615250)
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(time.parse "01.05.2024 12:30" "%d.%m.%Y %H:%M")"#)
+                .unwrap(),
+            Value::Int(1714566600000)
+        );
+        assert_eq!(
+            runtime
+                .eval_string(r#"(time.parse "2024-05-01" "%Y-%m-%d")"#)
+                .unwrap(),
+            Value::Int(1714521600000)
+        );
+
+        let err = runtime.eval_string(r#"(time.format t "%Q")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid time pattern: %Q");
+        let err = runtime
+            .eval_string(r#"(time.parse "yesterday")"#)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Cannot parse time \"yesterday\""));
+    }
+
+    #[test]
+    fn test_clocks() {
+        let mut runtime = Runtime::new_default();
+        let before = Utc::now().timestamp_millis();
+        let now = runtime.eval_string("(time.now)").unwrap().as_int();
+        assert!(now >= before && now - before < 1000);
+
+        runtime.eval_string("(def start (time.monotonic))").unwrap();
+        let elapsed = runtime
+            .eval_string("(time.measure (time.sleep 20) (def x 1))")
+            .unwrap()
+            .as_float();
+        assert!(elapsed >= 20.0);
+        assert_eq!(runtime.get_global("x"), Some(Value::Int(1)));
+        let diff = runtime
+            .eval_string("(- (time.monotonic) start)")
+            .unwrap()
+            .as_float();
+        assert!(diff >= elapsed);
+
+        let err = runtime.eval_string("(time.sleep -1)").unwrap_err();
+        assert_eq!(err.to_string(), "Cannot sleep for -1 milliseconds");
+        let err = runtime.eval_string("(time.sleep 1e300)").unwrap_err();
+        assert!(err.to_string().starts_with("Cannot sleep for 1000000"));
+    }
+}
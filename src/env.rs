@@ -1,14 +1,21 @@
-use crate::func::{FuncDef, FuncMetadata, FuncType};
+use crate::func::{FuncDef, FuncMetadata, FuncType, NativeFn};
 use crate::{
     func::FuncKind,
-    parser::{parse_tokens, Value},
+    optimizer,
+    parser::{parse_tokens, FuncValue, Value},
+    sandbox::Sandbox,
+    seq::LiveSources,
+    streams::Streams,
+    sync::{Lock, Shared},
     tokenizer::tokenize,
     utils::HierCellMapWrap,
 };
-use std::collections::HashSet;
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 type FuncsType = HierCellMapWrap<String, FuncDef>;
 type VarsType = HierCellMapWrap<String, Value>;
@@ -19,6 +26,19 @@ pub struct Environment {
     pub vars: VarsType,
     importing_files: HashSet<PathBuf>,
     current_file: Option<PathBuf>,
+    optimize: bool,
+    sandbox: Shared<Sandbox>,
+    streams: Shared<Streams>,
+    /// Generators created in this runtime, stopped by `Runtime`'s drop
+    live_sources: Shared<LiveSources>,
+    /// Object types whose methods can be called as `(type.method handle ...)`
+    object_types: Shared<Lock<BTreeSet<String>>>,
+    args: Shared<Vec<String>>,
+    script: Option<Shared<String>>,
+    rng: Shared<Lock<StdRng>>,
+    /// Variables set (`Some`) or removed (`None`) by `env.set`, on top of the
+    /// process environment, which is never changed
+    env_vars: Shared<Lock<BTreeMap<String, Option<String>>>>,
 }
 
 impl Debug for Environment {
@@ -40,6 +60,15 @@ impl Default for Environment {
             funcs: FuncsType::new_root(),
             importing_files: HashSet::new(),
             current_file: None,
+            optimize: false,
+            sandbox: Shared::new(Sandbox::default()),
+            streams: Shared::new(Streams::default()),
+            live_sources: Shared::default(),
+            object_types: Shared::default(),
+            args: Shared::new(Vec::new()),
+            script: None,
+            rng: Shared::new(Lock::new(StdRng::from_os_rng())),
+            env_vars: Shared::new(Lock::new(BTreeMap::new())),
         }
     }
 }
@@ -51,6 +80,34 @@ impl Environment {
             vars: self.vars.new_child(),
             importing_files: self.importing_files.clone(),
             current_file: self.current_file.clone(),
+            optimize: self.optimize,
+            sandbox: self.sandbox.clone(),
+            streams: self.streams.clone(),
+            live_sources: self.live_sources.clone(),
+            object_types: self.object_types.clone(),
+            args: self.args.clone(),
+            script: self.script.clone(),
+            rng: self.rng.clone(),
+            env_vars: self.env_vars.clone(),
+        }
+    }
+
+    /// Same environment on other scopes, streams and random generator, with a
+    /// copy of the environment variables, used when copying a runtime
+    pub(crate) fn with_scopes(
+        &self,
+        funcs: FuncsType,
+        vars: VarsType,
+        streams: Shared<Streams>,
+        rng: Shared<Lock<StdRng>>,
+    ) -> Self {
+        Self {
+            funcs,
+            vars,
+            streams,
+            rng,
+            env_vars: Shared::new(Lock::new(self.env_vars.borrow().clone())),
+            ..self.clone()
         }
     }
 
@@ -62,6 +119,122 @@ impl Environment {
         &self.vars
     }
 
+    /// Enables the `optimizer` pass for programs evaluated from source (including imports)
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Replaces the sandbox for this environment and the ones created from it afterwards
+    pub fn set_sandbox(&mut self, sandbox: Sandbox) {
+        self.sandbox = Shared::new(sandbox);
+    }
+
+    pub fn streams(&self) -> &Streams {
+        &self.streams
+    }
+
+    /// Replaces the streams for this environment and the ones created from it afterwards
+    pub fn set_streams(&mut self, streams: Streams) {
+        self.streams = Shared::new(streams);
+    }
+
+    /// Random generator of the `rnd.*` functions, shared by the whole runtime
+    pub fn rng(&self) -> &Lock<StdRng> {
+        &self.rng
+    }
+
+    /// Restarts the random generator, the same seed gives the same numbers
+    pub fn seed_rng(&self, seed: u64) {
+        self.rng.replace(StdRng::seed_from_u64(seed));
+    }
+
+    pub(crate) fn live_sources(&self) -> &LiveSources {
+        &self.live_sources
+    }
+
+    /// Starts tracking sources anew, used by forks: the generators they
+    /// share with their parent are stopped with the parent
+    pub(crate) fn detach_live_sources(&mut self) {
+        self.live_sources = Shared::default();
+    }
+
+    /// Command-line arguments of the script, returned by `sys.args`
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = Shared::new(args);
+    }
+
+    /// Path of the script run from the command line, returned by `sys.script`
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref().map(String::as_str)
+    }
+
+    pub fn set_script(&mut self, script: Option<String>) {
+        self.script = script.map(Shared::new);
+    }
+
+    /// Environment variable as seen by scripts: the value set with
+    /// `set_env_var` in this runtime, otherwise the one of the process
+    pub fn env_var(&self, name: &str) -> Option<String> {
+        match self.env_vars.borrow().get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var(name).ok(),
+        }
+    }
+
+    /// Sets (`None` removes) an environment variable for the scripts of this
+    /// runtime and the programs they run. The process environment is not
+    /// changed, other runtimes do not see the variable.
+    pub fn set_env_var(&self, name: &str, value: Option<String>) {
+        self.env_vars.borrow_mut().insert(name.to_owned(), value);
+    }
+
+    /// All environment variables as seen by scripts, see `env_var`
+    pub fn env_vars(&self) -> BTreeMap<String, String> {
+        let mut vars: BTreeMap<_, _> = std::env::vars_os()
+            .map(|(name, value)| {
+                (
+                    name.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        for (name, value) in self.env_vars.borrow().iter() {
+            match value {
+                Some(value) => vars.insert(name.clone(), value.clone()),
+                None => vars.remove(name),
+            };
+        }
+        vars
+    }
+
+    /// Passes the variables set with `set_env_var` to a program
+    pub(crate) fn apply_env_vars(&self, command: &mut Command) {
+        for (name, value) in self.env_vars.borrow().iter() {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+    }
+
+    /// Parses a program and runs the optimizer over it when enabled
+    pub fn parse_program(&self, prog: &str) -> anyhow::Result<Vec<Value>> {
+        let tokens = tokenize(prog)?;
+        let parsed = parse_tokens(&tokens)?;
+        if self.optimize {
+            return Ok(optimizer::optimize(&parsed, self));
+        }
+        Ok(parsed)
+    }
+
     pub fn eval(&mut self, value: &Value) -> anyhow::Result<Value> {
         log::debug!("[EVAL] Expression: {:?}", value);
         let res = match value {
@@ -83,9 +256,28 @@ impl Environment {
         if let Some(val) = self.funcs.get(name_ref) {
             return Ok(Value::Func(val.kind));
         }
+        if let Some(val) = self.file_binding(name) {
+            return Ok(val);
+        }
         anyhow::bail!("Undeclared variable or function: {}", name)
     }
 
+    /// `__file__` and `__dir__`: the file being evaluated (or the file a
+    /// function was defined in) and its directory, nil outside of files
+    fn file_binding(&self, name: &str) -> Option<Value> {
+        let path = match name {
+            "__file__" => self.current_file.as_deref(),
+            "__dir__" => self.current_file.as_deref().and_then(|file| file.parent()),
+            _ => return None,
+        };
+        Some(path.map_or(Value::Nil, |path| Value::String(path.to_string_lossy().into_owned())))
+    }
+
+    /// File being evaluated, set by `Runtime::eval_file` and `import`
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current_file.as_deref()
+    }
+
     pub fn get_func_def(&self, name: &str) -> anyhow::Result<FuncDef> {
         let name_ref = &name.to_string();
         if let Some(val) = self.funcs.get(name_ref) {
@@ -143,10 +335,38 @@ impl Environment {
     }
 
     pub fn eval_func(&mut self, name: &str, args: &[Value]) -> anyhow::Result<Value> {
-        let fd = self.get_func_def(name)?;
+        let fd = match self.get_func_def(name) {
+            Ok(fd) => fd,
+            Err(err) => return self.eval_method(name, args).unwrap_or(Err(err)),
+        };
         self.eval_any_func(fd, args)
     }
 
+    /// Lets scripts call the methods of objects of the type as
+    /// `(type.method handle args...)`, like `(obj.method handle args...)`
+    pub fn register_object_type(&self, name: &str) {
+        self.object_types.borrow_mut().insert(name.to_owned());
+    }
+
+    /// Calls `(obj.method handle args...)` or `(type.method handle args...)`
+    /// on a host object, `None` when the call is not a method call. Nothing
+    /// is evaluated unless the prefix is `obj` or a registered object type.
+    fn eval_method(&mut self, name: &str, args: &[Value]) -> Option<anyhow::Result<Value>> {
+        let (prefix, method) = name.split_once('.')?;
+        if prefix != "obj" && !self.object_types.borrow().contains(prefix) {
+            return None;
+        }
+        let handle = match self.eval(args.first()?) {
+            Ok(Value::Object(obj)) if prefix == "obj" || prefix == obj.type_name() => obj,
+            Ok(_) => return None,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(
+            self.eval_args(&args[1..])
+                .and_then(|values| handle.call_method(method, &values, self)),
+        )
+    }
+
     fn eval_any_func(&mut self, func: FuncDef, args: &[Value]) -> anyhow::Result<Value> {
         let metadata = &func.metadata;
         log::debug!(
@@ -167,14 +387,12 @@ impl Environment {
                 native_fn(args, env)?
             }
             FuncKind::Closure(func_val, captured_env) => {
-                let mut closure_env = captured_env.make_child();
-                for (i, param) in func_val.args.iter().enumerate() {
-                    if i < args.len() {
-                        let value = self.eval(&args[i])?;
-                        closure_env.vars.set(param, &value);
-                    }
-                }
-                closure_env.eval(&func_val.body)?
+                let values = self.eval_args(&args[..args.len().min(func_val.args.len())])?;
+                Self::call_closure(&func_val, &captured_env, &values)?
+            }
+            FuncKind::Memoized(memo) => {
+                let values = self.eval_args(args)?;
+                memo.call(self, &values)?
             }
         };
 
@@ -193,6 +411,50 @@ impl Environment {
         Ok(final_result)
     }
 
+    fn call_closure(func_val: &FuncValue, captured_env: &Environment, values: &[Value]) -> anyhow::Result<Value> {
+        let mut closure_env = captured_env.make_child();
+        for (param, value) in func_val.args.iter().zip(values) {
+            closure_env.vars.set(param, value);
+        }
+        closure_env.eval(&func_val.body)
+    }
+
+    /// Calls a function with already evaluated arguments.
+    /// Native functions receive expressions, so lists, symbols and functions
+    /// are bound to hidden variables in the call environment and passed by
+    /// name, evaluating them gives back the original values.
+    pub fn apply(&mut self, func: &FuncKind, args: &[Value]) -> anyhow::Result<Value> {
+        match func {
+            FuncKind::Native(native_fn) => {
+                let mut native_env = self.make_child();
+                let exprs: Vec<Value> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| match value {
+                        Value::List(_) | Value::Symbol(_) | Value::Func(_) => {
+                            let name = format!("__arg{}", i);
+                            native_env.vars.set(&name, value);
+                            Value::Symbol(name)
+                        }
+                        _ => value.clone(),
+                    })
+                    .collect();
+                native_fn(&exprs, &mut native_env)
+            }
+            FuncKind::Closure(func_val, captured_env) => Self::call_closure(func_val, captured_env, args),
+            FuncKind::Memoized(memo) => memo.call(self, args),
+        }
+    }
+
+    /// Like `apply`, but natives registered with `same_env` (def, fn, import, ...)
+    /// run in this environment and get the arguments unchanged, as expressions.
+    pub(crate) fn apply_def(&mut self, func: &FuncDef, args: &[Value]) -> anyhow::Result<Value> {
+        match &func.kind {
+            FuncKind::Native(native_fn) if func.metadata.same_env => native_fn(args, self),
+            kind => self.apply(kind, args),
+        }
+    }
+
     pub fn eval_args(&mut self, args: &[Value]) -> anyhow::Result<Vec<Value>> {
         log::debug!("[EVAL] args: {:?}", args);
         let mut evaluated = Vec::new();
@@ -203,6 +465,14 @@ impl Environment {
     }
 
     pub fn add_native(&mut self, name: &str, func: FuncType, same_env: bool) {
+        self.add_native_closure(name, func, same_env)
+    }
+
+    /// Registers a native function that may capture state (counters, handles, configuration)
+    pub fn add_native_closure<F>(&mut self, name: &str, func: F, same_env: bool)
+    where
+        F: NativeFn + 'static,
+    {
         let metadata = FuncMetadata {
             name: name.to_string(),
             same_env,
@@ -210,7 +480,7 @@ impl Environment {
         log::debug!("[ADD] native function: {:?}", metadata);
         let df = FuncDef {
             metadata,
-            kind: FuncKind::Native(func),
+            kind: FuncKind::native(func),
         };
 
         self.funcs.set(&name.to_string(), &df)
@@ -227,8 +497,7 @@ impl Environment {
             self.current_file = Some(path.clone());
         }
 
-        let tokens = tokenize(prog)?;
-        let parsed = parse_tokens(&tokens)?;
+        let parsed = self.parse_program(prog)?;
 
         let mut final_res = Value::Nil;
         for expr in &parsed {
@@ -249,34 +518,40 @@ impl Environment {
         Ok(final_res)
     }
 
+    /// Directory relative paths of scripts are resolved against: the directory
+    /// of the current file, or the working directory outside of files
+    pub fn base_dir(&self) -> anyhow::Result<PathBuf> {
+        if let Some(parent_dir) = self.current_file.as_ref().and_then(|file| file.parent()) {
+            return Ok(parent_dir.to_path_buf());
+        }
+        std::env::current_dir().map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))
+    }
+
+    /// Makes a path used by a script absolute, see `base_dir`
+    pub fn resolve_path(&self, file_path: &str) -> anyhow::Result<PathBuf> {
+        let path = PathBuf::from(file_path);
+        if path.is_absolute() {
+            return Ok(path);
+        }
+        Ok(self.base_dir()?.join(path))
+    }
+
+    /// Resolves a path and checks that the sandbox lets scripts access it
+    pub fn checked_path(&self, file_path: &str) -> anyhow::Result<PathBuf> {
+        let path = self.resolve_path(file_path)?;
+        self.sandbox.check_access(&path)?;
+        Ok(path)
+    }
+
     pub fn import_file(&mut self, file_path: &str) -> anyhow::Result<Value> {
         use std::fs;
-        use std::env;
-
-        // Resolve path - start with the path as given
-        let mut path = PathBuf::from(file_path);
-        
-        // If path is relative, resolve relative to current file's directory (if available)
-        // Otherwise fall back to current working directory
-        if !path.is_absolute() {
-            if let Some(ref current_file) = self.current_file {
-                // Resolve relative to the directory of the current file
-                if let Some(parent_dir) = current_file.parent() {
-                    path = parent_dir.join(&path);
-                } else {
-                    // Current file has no parent (shouldn't happen, but handle gracefully)
-                    let current_dir = env::current_dir()
-                        .map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))?;
-                    path = current_dir.join(&path);
-                }
-            } else {
-                // No current file context, use working directory
-                let current_dir = env::current_dir()
-                    .map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))?;
-                path = current_dir.join(&path);
-            }
+
+        if !self.sandbox.fs_allowed() {
+            anyhow::bail!("Cannot import {}: filesystem access is denied", file_path);
         }
 
+        let mut path = self.resolve_path(file_path)?;
+
         // Try the path as-is first
         if !path.exists() {
             // Try with .lsp extension
@@ -292,6 +567,8 @@ impl Environment {
         let canonical_path = path.canonicalize()
             .map_err(|e| anyhow::anyhow!("Failed to canonicalize path {}: {}", path.display(), e))?;
 
+        self.sandbox.check_path(&canonical_path)?;
+
         // Check for circular import
         if self.importing_files.contains(&canonical_path) {
             anyhow::bail!("Circular import detected: {}", canonical_path.display());
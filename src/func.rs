@@ -1,10 +1,21 @@
 use crate::{
     env::Environment,
+    memo::Memo,
     parser::{FuncValue, Value},
+    sync::{MaybeSendSync, Shared},
 };
 
 pub type FuncType = fn(args: &[Value], fenv: &mut Environment) -> anyhow::Result<Value>;
 
+/// Closure callable as a native function (`Send + Sync` with the `sync` feature)
+pub trait NativeFn: Fn(&[Value], &mut Environment) -> anyhow::Result<Value> + MaybeSendSync {}
+
+impl<F> NativeFn for F where F: Fn(&[Value], &mut Environment) -> anyhow::Result<Value> + MaybeSendSync {}
+
+/// Native function as stored in the environment. Plain `FuncType` pointers
+/// are wrapped as well, so natives may capture host state.
+pub type NativeFunc = Shared<dyn NativeFn>;
+
 #[derive(Clone, Debug)]
 pub struct FuncDef {
     pub metadata: FuncMetadata,
@@ -26,6 +37,7 @@ impl FuncDef {
         match &self.kind {
             FuncKind::Native(_) => "native",
             FuncKind::Closure(..) => "closure",
+            FuncKind::Memoized(_) => "memoized",
         }
     }
 
@@ -42,8 +54,18 @@ pub struct FuncMetadata {
 
 #[derive(Clone)]
 pub enum FuncKind {
-    Native(FuncType),
+    Native(NativeFunc),
     Closure(FuncValue, Environment),
+    Memoized(Shared<Memo>),
+}
+
+impl FuncKind {
+    pub fn native<F>(func: F) -> Self
+    where
+        F: NativeFn + 'static,
+    {
+        FuncKind::Native(Shared::new(func))
+    }
 }
 
 impl PartialEq for FuncKind {
@@ -63,6 +85,7 @@ impl std::fmt::Debug for FuncKind {
         match self {
             FuncKind::Native(_) => write!(f, "(nat fn)"),
             FuncKind::Closure(df, _) => write!(f, "(closure fn {:?}, {:?})", df.args, df.body),
+            FuncKind::Memoized(memo) => write!(f, "{:?}", memo),
         }
     }
 }
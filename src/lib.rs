@@ -1,10 +1,27 @@
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod cli;
+pub mod convert;
 pub mod corelib;
 pub mod env;
-mod func;
+pub mod func;
+mod memo;
+pub mod object;
+pub mod optimizer;
 pub mod parser;
 pub mod runtime;
+pub mod sandbox;
+mod seq;
+#[cfg(feature = "serde")]
+mod serde_value;
+pub mod snapshot;
+pub mod streams;
+pub mod sync;
 pub mod tokenizer;
 mod utils;
 
-pub use crate::runtime::Runtime;
+pub use crate::convert::{FromValue, IntoValue};
+pub use crate::runtime::{Runtime, RuntimeBuilder};
+pub use crate::snapshot::Snapshot;
+#[cfg(feature = "serde")]
+pub use crate::serde_value::{from_value, to_value};
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{env::Environment, func::FuncKind, parser::Value, sync::Lock};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: usize,
+    pub capacity: Option<usize>,
+}
+
+#[derive(Clone)]
+struct MemoEntry {
+    value: Value,
+    last_used: u64,
+}
+
+#[derive(Clone, Default)]
+struct MemoCache {
+    entries: HashMap<String, MemoEntry>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+/**
+ * Function wrapper caching results by the structure of its (evaluated)
+ * arguments. With a capacity the least recently used entry is evicted
+ * first. Calls with functions, sequences or objects among the arguments are
+ * not cached, as those cannot be compared structurally.
+ */
+pub struct Memo {
+    func: FuncKind,
+    capacity: Option<usize>,
+    cache: Lock<MemoCache>,
+}
+
+impl Memo {
+    pub fn new(func: FuncKind, capacity: Option<usize>) -> Self {
+        Self {
+            func,
+            capacity,
+            cache: Lock::default(),
+        }
+    }
+
+    /// Memo around another function starting with a copy of this cache
+    pub fn with_func(&self, func: FuncKind) -> Self {
+        Self {
+            func,
+            capacity: self.capacity,
+            cache: Lock::new(self.cache.borrow().clone()),
+        }
+    }
+
+    pub fn func(&self) -> &FuncKind {
+        &self.func
+    }
+
+    pub fn call(&self, env: &mut Environment, args: &[Value]) -> anyhow::Result<Value> {
+        let Some(key) = memo_key(args) else {
+            self.cache.borrow_mut().misses += 1;
+            return env.apply(&self.func, args);
+        };
+
+        {
+            let mut cache = self.cache.borrow_mut();
+            cache.clock += 1;
+            let clock = cache.clock;
+            if let Some(entry) = cache.entries.get_mut(&key) {
+                entry.last_used = clock;
+                let value = entry.value.clone();
+                cache.hits += 1;
+                return Ok(value);
+            }
+            cache.misses += 1;
+        }
+
+        // the cache must not be borrowed here, the function may call itself
+        let value = env.apply(&self.func, args)?;
+
+        let mut cache = self.cache.borrow_mut();
+        if let Some(capacity) = self.capacity {
+            if capacity == 0 {
+                return Ok(value);
+            }
+            while cache.entries.len() >= capacity {
+                let oldest = cache
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone());
+                match oldest {
+                    Some(oldest) => cache.entries.remove(&oldest),
+                    None => break,
+                };
+            }
+        }
+        let last_used = cache.clock;
+        cache.entries.insert(
+            key,
+            MemoEntry {
+                value: value.clone(),
+                last_used,
+            },
+        );
+        Ok(value)
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        let cache = self.cache.borrow();
+        MemoStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            size: cache.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    pub fn clear(&self) {
+        self.cache.replace(MemoCache::default());
+    }
+}
+
+impl fmt::Debug for Memo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(memo {:?})", self.func)
+    }
+}
+
+fn memo_key(args: &[Value]) -> Option<String> {
+    if args.iter().any(contains_unkeyable) {
+        return None;
+    }
+    Some(format!("{:?}", args))
+}
+
+fn contains_unkeyable(value: &Value) -> bool {
+    match value {
+        Value::Func(_) | Value::Seq(_) | Value::Object(_) => true,
+        Value::List(items) => items.iter().any(contains_unkeyable),
+        Value::Map(items) => items.values().any(contains_unkeyable),
+        _ => false,
+    }
+}
@@ -0,0 +1,359 @@
+use std::{any::Any, collections::BTreeMap, fmt};
+
+use crate::{
+    env::Environment,
+    parser::Value,
+    sync::{DynAny, MaybeSendSync, Shared},
+};
+
+pub trait MethodFn:
+    Fn(&Object, &[Value], &mut Environment) -> anyhow::Result<Value> + MaybeSendSync
+{
+}
+
+impl<F> MethodFn for F where
+    F: Fn(&Object, &[Value], &mut Environment) -> anyhow::Result<Value> + MaybeSendSync
+{
+}
+
+/// Method of a host object, called with the object and the evaluated arguments
+pub type MethodFunc = Shared<dyn MethodFn>;
+
+trait DisplayFn: Fn(&Object) -> String + MaybeSendSync {}
+
+impl<F: Fn(&Object) -> String + MaybeSendSync> DisplayFn for F {}
+
+type DisplayFunc = Shared<dyn DisplayFn>;
+
+/**
+ * Describes a kind of host object: its name, how it is displayed and the
+ * methods scripts may call with `(obj.method handle args...)`.
+ * One `ObjectType` is usually shared by all objects of a Rust type.
+ */
+pub struct ObjectType {
+    name: String,
+    display: Option<DisplayFunc>,
+    methods: BTreeMap<String, MethodFunc>,
+}
+
+impl ObjectType {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            display: None,
+            methods: BTreeMap::new(),
+        }
+    }
+
+    /// Sets how the objects are printed, `<name>` is used otherwise
+    pub fn with_display<T, F>(mut self, func: F) -> Self
+    where
+        T: 'static,
+        F: Fn(&T) -> String + MaybeSendSync + 'static,
+    {
+        let name = self.name.clone();
+        self.display = Some(Shared::new(move |obj| match obj.downcast_ref::<T>() {
+            Some(data) => func(data),
+            None => format!("<{}>", name),
+        }));
+        self
+    }
+
+    /// Adds a method receiving the object data downcast to `T`
+    pub fn with_method<T, F>(mut self, name: &str, func: F) -> Self
+    where
+        T: 'static,
+        F: Fn(&T, &[Value], &mut Environment) -> anyhow::Result<Value> + MaybeSendSync + 'static,
+    {
+        let type_name = self.name.clone();
+        let method_name = name.to_owned();
+        self.methods.insert(
+            name.to_owned(),
+            Shared::new(move |obj, args, fenv| match obj.downcast_ref::<T>() {
+                Some(data) => func(data, args, fenv),
+                None => anyhow::bail!("{}.{}: unexpected object data", type_name, method_name),
+            }),
+        );
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn method(&self, name: &str) -> Option<MethodFunc> {
+        self.methods.get(name).cloned()
+    }
+
+    /// Method names, sorted
+    pub fn method_names(&self) -> Vec<String> {
+        self.methods.keys().cloned().collect()
+    }
+}
+
+/**
+ * Opaque Rust value passed through scripts (a file handle, a connection,
+ * a domain struct). Scripts can only print it, pass it around and call its
+ * methods; natives downcast it back to the Rust type.
+ * Objects are shared, cloning the value clones the handle.
+ * With the `sync` feature the data must be `Send + Sync`.
+ */
+#[derive(Clone)]
+pub struct Object {
+    data: Shared<DynAny>,
+    object_type: Shared<ObjectType>,
+}
+
+impl Object {
+    pub fn new<T: Any + MaybeSendSync>(object_type: Shared<ObjectType>, data: T) -> Self {
+        Self {
+            data: Shared::new(data),
+            object_type,
+        }
+    }
+
+    /// Object with a type name only, without display or methods
+    pub fn plain<T: Any + MaybeSendSync>(type_name: &str, data: T) -> Self {
+        Self::new(Shared::new(ObjectType::new(type_name)), data)
+    }
+
+    pub fn type_name(&self) -> &str {
+        self.object_type.name()
+    }
+
+    pub fn object_type(&self) -> &Shared<ObjectType> {
+        &self.object_type
+    }
+
+    pub fn is<T: 'static>(&self) -> bool {
+        self.data.is::<T>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.data.downcast_ref::<T>()
+    }
+
+    /// Shared handle to the data, for natives keeping the object around
+    pub fn downcast_rc<T: Any + MaybeSendSync>(&self) -> Option<Shared<T>> {
+        self.data.clone().downcast::<T>().ok()
+    }
+
+    pub fn call_method(
+        &self,
+        name: &str,
+        args: &[Value],
+        fenv: &mut Environment,
+    ) -> anyhow::Result<Value> {
+        match self.object_type.method(name) {
+            Some(method) => method(self, args, fenv),
+            None => anyhow::bail!("Object {} has no method: {}", self.type_name(), name),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.object_type.display {
+            Some(display) => write!(f, "{}", display(self)),
+            None => write!(f, "<{}>", self.type_name()),
+        }
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(object {})", self.type_name())
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl PartialOrd for Object {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "sync"))]
+    use std::cell::Cell;
+    #[cfg(feature = "sync")]
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use super::*;
+    use crate::runtime::Runtime;
+
+    // objects need not be `Send + Sync` without the `sync` feature
+    #[cfg(not(feature = "sync"))]
+    struct Counter {
+        count: Cell<i64>,
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn counter_type() -> Shared<ObjectType> {
+        Shared::new(
+            ObjectType::new("counter")
+                .with_display(|c: &Counter| format!("<counter {}>", c.count.get()))
+                .with_method("inc", |c: &Counter, args, _fenv| {
+                    let by = args.first().map_or(1, Value::as_int);
+                    c.count.set(c.count.get() + by);
+                    Ok(Value::Int(c.count.get()))
+                })
+                .with_method("get", |c: &Counter, _args, _fenv| {
+                    Ok(Value::Int(c.count.get()))
+                }),
+        )
+    }
+
+    #[cfg(not(feature = "sync"))]
+    #[test]
+    fn test_object_methods() {
+        let mut runtime = Runtime::new_default();
+        let counter = Object::new(
+            counter_type(),
+            Counter {
+                count: Cell::new(0),
+            },
+        );
+        runtime.set_global("c", Value::Object(counter.clone()));
+        runtime.register_object_type(counter.object_type());
+
+        let result = runtime
+            .eval_string("((obj.inc c) (obj.inc c 10) (counter.inc c) (obj.get c))")
+            .unwrap();
+        assert_eq!(result, Value::Int(12));
+        assert_eq!(counter.downcast_ref::<Counter>().unwrap().count.get(), 12);
+        assert_eq!(
+            runtime.eval_string("(cast.string c)").unwrap(),
+            Value::String("<counter 12>".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(obj.type c)").unwrap(),
+            Value::String("counter".to_string())
+        );
+        assert_eq!(
+            runtime.eval_parsed("(obj.methods c)").unwrap(),
+            Value::List(vec![
+                Value::String("get".to_string()),
+                Value::String("inc".to_string())
+            ])
+        );
+    }
+
+    #[cfg(not(feature = "sync"))]
+    #[test]
+    fn test_object_errors() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global(
+            "c",
+            Value::Object(Object::new(
+                counter_type(),
+                Counter {
+                    count: Cell::new(0),
+                },
+            )),
+        );
+        runtime.set_global("p", Value::Object(Object::plain("point", (1, 2))));
+
+        let err = runtime.eval_string("(obj.reset c)").unwrap_err();
+        assert_eq!(err.to_string(), "Object counter has no method: reset");
+        let err = runtime.eval_string("(point.inc c)").unwrap_err();
+        assert_eq!(err.to_string(), "Undeclared function: point.inc");
+        // the argument of an unknown call is not evaluated
+        let err = runtime.eval_string("(foo.bar (def n 1))").unwrap_err();
+        assert_eq!(err.to_string(), "Undeclared function: foo.bar");
+        assert_eq!(runtime.get_global("n"), None);
+        assert!(runtime.eval_string("(obj.inc 1)").is_err());
+        assert_eq!(
+            runtime.eval_string("(cast.string p)").unwrap(),
+            Value::String("<point>".to_string())
+        );
+    }
+
+    #[cfg(feature = "sync")]
+    struct SyncCounter {
+        count: AtomicI64,
+    }
+
+    #[cfg(feature = "sync")]
+    fn sync_counter_type() -> Shared<ObjectType> {
+        Shared::new(
+            ObjectType::new("counter")
+                .with_display(|c: &SyncCounter| format!("<counter {}>", c.count.load(Ordering::Relaxed)))
+                .with_method("inc", |c: &SyncCounter, args, _fenv| {
+                    let by = args.first().map_or(1, Value::as_int);
+                    Ok(Value::Int(c.count.fetch_add(by, Ordering::Relaxed) + by))
+                })
+                .with_method("get", |c: &SyncCounter, _args, _fenv| {
+                    Ok(Value::Int(c.count.load(Ordering::Relaxed)))
+                }),
+        )
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_object_methods() {
+        let mut runtime = Runtime::new_default();
+        let counter = Object::new(
+            sync_counter_type(),
+            SyncCounter {
+                count: AtomicI64::new(0),
+            },
+        );
+        runtime.set_global("c", Value::Object(counter.clone()));
+        runtime.register_object_type(counter.object_type());
+
+        let result = runtime
+            .eval_string("((obj.inc c) (obj.inc c 10) (counter.inc c) (obj.get c))")
+            .unwrap();
+        assert_eq!(result, Value::Int(12));
+        assert_eq!(counter.downcast_ref::<SyncCounter>().unwrap().count.load(Ordering::Relaxed), 12);
+        assert_eq!(
+            runtime.eval_string("(cast.string c)").unwrap(),
+            Value::String("<counter 12>".to_string())
+        );
+        assert_eq!(
+            runtime.eval_string("(obj.type c)").unwrap(),
+            Value::String("counter".to_string())
+        );
+        assert_eq!(
+            runtime.eval_parsed("(obj.methods c)").unwrap(),
+            Value::List(vec![
+                Value::String("get".to_string()),
+                Value::String("inc".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_native_downcast() {
+        let mut runtime = Runtime::new_default();
+        runtime.register_fn("point.new", |args, fenv| {
+            let evl = fenv.eval_args(args)?;
+            Ok(Value::Object(Object::plain(
+                "point",
+                (evl[0].as_int(), evl[1].as_int()),
+            )))
+        });
+        runtime.register_fn("point.x", |args, fenv| match fenv.eval(&args[0])? {
+            Value::Object(obj) => match obj.downcast_ref::<(i64, i64)>() {
+                Some((x, _)) => Ok(Value::Int(*x)),
+                None => anyhow::bail!("point.x: expected a point, got {}", obj.type_name()),
+            },
+            other => anyhow::bail!("point.x: expected a point, got {}", other.type_name()),
+        });
+
+        assert_eq!(
+            runtime.eval_string("(point.x (point.new 3 4))").unwrap(),
+            Value::Int(3)
+        );
+        let p = runtime.eval_string("(def p (point.new 1 2))").unwrap();
+        assert_eq!(runtime.get_global("p"), Some(p));
+        assert_eq!(runtime.eval_string("(== p p)").unwrap(), Value::Bool(true));
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{env::Environment, func::FuncKind, parser::Value};
+
+/// Pure operators from `corelib::ops` that can be evaluated ahead of time
+/// when all of their arguments are literals.
+const FOLDABLE_OPS: &[&str] = &[
+    "+", "-", "*", "/", "%", "==", "!=", "<", ">", "<=", ">=", "&&", "||", "not",
+];
+
+/// Natives that inspect the raw shape of their arguments instead of
+/// evaluating them, so their arguments are left untouched.
+const RAW_ARG_FORMS: &[&str] = &["head", "last", "internal.func.nat.call"];
+
+/**
+ * Optimization pass run between `parser::parse_tokens` and evaluation.
+ *
+ * - folds calls of pure operators whose arguments are all literals
+ * - prunes `if` branches whose condition is a literal
+ * - inlines top-level `def`s of literals that are never rebound, except
+ *   into function bodies, which may run after a later program redefines them
+ *
+ * A builtin is only touched if it still resolves to the native registered
+ * in `env` and the program does not rebind its name.
+ */
+pub fn optimize(exprs: &[Value], env: &Environment) -> Vec<Value> {
+    let mut optimizer = Optimizer::new(exprs, env);
+    exprs
+        .iter()
+        .map(|expr| optimizer.expr(expr, true, true))
+        .collect()
+}
+
+struct Optimizer<'a> {
+    env: &'a Environment,
+    /// Number of `def`s per name anywhere in the program
+    defs: HashMap<String, usize>,
+    /// Names bound by anything else than `def` (functions, params, loop vars, undef)
+    bound: HashSet<String>,
+    /// Known constant values of inlinable variables
+    constants: HashMap<String, Value>,
+    has_import: bool,
+    /// Number of enclosing `fn`/`fn-memo` bodies, constants are not inlined there
+    fn_depth: usize,
+}
+
+impl<'a> Optimizer<'a> {
+    fn new(exprs: &[Value], env: &'a Environment) -> Self {
+        let mut optimizer = Self {
+            env,
+            defs: HashMap::new(),
+            bound: HashSet::new(),
+            constants: HashMap::new(),
+            has_import: false,
+            fn_depth: 0,
+        };
+        for expr in exprs {
+            optimizer.collect_bindings(expr);
+        }
+        optimizer
+    }
+
+    fn collect_bindings(&mut self, expr: &Value) {
+        let Value::List(list) = expr else {
+            return;
+        };
+
+        if let Some(Value::Symbol(head)) = list.first() {
+            let args = &list[1..];
+            match head.as_str() {
+                "def" => {
+                    if let Some(name) = args.first() {
+                        *self.defs.entry(name.as_string()).or_default() += 1;
+                    }
+                }
+                "undef" | "for" => {
+                    if let Some(name) = args.first() {
+                        self.bound.insert(name.as_string());
+                    }
+                }
+                "fn" | "fn-memo" => {
+                    let params = match args.first() {
+                        Some(Value::List(params)) => Some(params),
+                        Some(name) => {
+                            self.bound.insert(name.as_string());
+                            match args.get(1) {
+                                Some(Value::List(params)) => Some(params),
+                                _ => None,
+                            }
+                        }
+                        None => None,
+                    };
+                    for param in params.into_iter().flatten() {
+                        self.bound.insert(param.as_string());
+                    }
+                }
+                "import" => self.has_import = true,
+                _ => {}
+            }
+        }
+
+        for item in list {
+            self.collect_bindings(item);
+        }
+    }
+
+    fn is_rebound(&self, name: &str) -> bool {
+        self.defs.contains_key(name) || self.bound.contains(name)
+    }
+
+    /// Checks that `name` still refers to the native builtin. An imported
+    /// file may redefine any builtin, so nothing is a known builtin after
+    /// an `import`.
+    fn is_builtin(&self, name: &str) -> bool {
+        let key = name.to_string();
+        !self.has_import
+            && !self.is_rebound(name)
+            && self.env.vars().get(&key).is_none()
+            && matches!(
+                self.env.funcs().get(&key).map(|fd| fd.kind),
+                Some(FuncKind::Native(_))
+            )
+    }
+
+    fn is_inlinable(&self, name: &str) -> bool {
+        self.defs.get(name) == Some(&1)
+            && !self.bound.contains(name)
+            && self.env.vars().get(&name.to_string()).is_none()
+            && self.is_builtin("def")
+    }
+
+    /**
+     * `replaceable` is false when the expression sits in a list whose shape
+     * matters to `Environment::eval_list` (e.g. a do-block), where a call
+     * may only be replaced by another list.
+     * `top_level` is true when the expression is always evaluated, in order.
+     */
+    fn expr(&mut self, expr: &Value, replaceable: bool, top_level: bool) -> Value {
+        match expr {
+            Value::Symbol(name) if self.fn_depth == 0 => self
+                .constants
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| expr.clone()),
+            Value::List(list) => self.list(list, replaceable, top_level),
+            _ => expr.clone(),
+        }
+    }
+
+    fn list(&mut self, list: &[Value], replaceable: bool, top_level: bool) -> Value {
+        match list.first() {
+            Some(Value::Symbol(head)) => self.call(head, &list[1..], replaceable, top_level),
+            Some(Value::List(_)) => {
+                let is_block = list.iter().all(Value::is_list);
+                Value::List(
+                    list.iter()
+                        .map(|item| self.expr(item, false, top_level && is_block))
+                        .collect(),
+                )
+            }
+            _ => Value::List(list.iter().map(|item| self.expr(item, true, false)).collect()),
+        }
+    }
+
+    fn call(&mut self, head: &str, args: &[Value], replaceable: bool, top_level: bool) -> Value {
+        let rebuild = |args: Vec<Value>| {
+            let mut list = vec![Value::Symbol(head.to_owned())];
+            list.extend(args);
+            Value::List(list)
+        };
+
+        if RAW_ARG_FORMS.contains(&head) {
+            return rebuild(args.to_vec());
+        }
+
+        match head {
+            "fn" | "fn-memo" if self.is_builtin(head) => {
+                let body_pos = if args.first().is_some_and(Value::is_list) { 1 } else { 2 };
+                self.fn_depth += 1;
+                let args = self.args_from(args, body_pos);
+                self.fn_depth -= 1;
+                return rebuild(args);
+            }
+            "for" if self.is_builtin(head) => return rebuild(self.args_from(args, 1)),
+            "undef" if self.is_builtin(head) => return rebuild(args.to_vec()),
+            "def" if self.is_builtin(head) => {
+                let args = self.args_from(args, 1);
+                if let (true, Some(name), Some(value)) = (top_level, args.first(), args.get(1)) {
+                    let name = name.as_string();
+                    if is_literal(value) && self.is_inlinable(&name) {
+                        self.constants.insert(name, value.clone());
+                    }
+                }
+                return rebuild(args);
+            }
+            "if" if self.is_builtin(head) => {
+                let args: Vec<Value> = args.iter().map(|arg| self.expr(arg, true, false)).collect();
+                if let Some(cond) = args.first().filter(|cond| is_literal(cond)) {
+                    let branch = if cond.as_bool() { args.get(1) } else { args.get(2) };
+                    let pruned = branch.cloned().unwrap_or(Value::Nil);
+                    if replaceable || pruned.is_list() {
+                        return pruned;
+                    }
+                    if pruned.is_nil() {
+                        return Value::List(vec![]);
+                    }
+                }
+                return rebuild(args);
+            }
+            _ => {}
+        }
+
+        let args: Vec<Value> = args.iter().map(|arg| self.expr(arg, true, false)).collect();
+        if replaceable && FOLDABLE_OPS.contains(&head) && self.is_builtin(head) && can_fold(head, &args) {
+            let mut scratch = self.env.make_child();
+            if let Ok(folded) = scratch.eval_func(head, &args) {
+                log::debug!("[OPT] Folded ({} {:?}) => {:?}", head, args, folded);
+                return folded;
+            }
+        }
+        rebuild(args)
+    }
+
+    /// Optimizes arguments starting at `from`, leaving the preceding ones (names, params) as-is
+    fn args_from(&mut self, args: &[Value], from: usize) -> Vec<Value> {
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                if i < from {
+                    arg.clone()
+                } else {
+                    self.expr(arg, true, false)
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_literal(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Int(_) | Value::Float(_) | Value::String(_) | Value::Bool(_) | Value::Nil
+    )
+}
+
+fn can_fold(op: &str, args: &[Value]) -> bool {
+    if !args.iter().all(is_literal) {
+        return false;
+    }
+
+    match op {
+        "+" | "-" | "*" | "/" | "%" => arithmetic_is_safe(op, args),
+        "not" => args.len() == 1,
+        "&&" | "||" => true,
+        _ => !args.is_empty(),
+    }
+}
+
+/// Rejects integer arithmetic that would overflow or divide by zero at fold time
+fn arithmetic_is_safe(op: &str, args: &[Value]) -> bool {
+    if args.iter().any(Value::is_string) {
+        return op == "+";
+    }
+    if args.iter().any(Value::is_float) {
+        return true;
+    }
+
+    let ints: Vec<i64> = args.iter().map(Value::as_int).collect();
+    let Some((first, rest)) = ints.split_first() else {
+        return true;
+    };
+    let checked: fn(i64, i64) -> Option<i64> = match op {
+        "+" => i64::checked_add,
+        "-" => i64::checked_sub,
+        "*" => i64::checked_mul,
+        "/" => i64::checked_div,
+        _ => i64::checked_rem,
+    };
+    rest.iter()
+        .try_fold(*first, |acc, x| checked(acc, *x))
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse_tokens, runtime::Runtime, tokenizer::tokenize};
+
+    fn optimize_str(runtime: &Runtime, prog: &str) -> Vec<Value> {
+        let parsed = parse_tokens(&tokenize(prog).unwrap()).unwrap();
+        optimize(&parsed, runtime.env())
+    }
+
+    fn parse_str(prog: &str) -> Vec<Value> {
+        parse_tokens(&tokenize(prog).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_folds_arithmetic_and_comparisons() {
+        let runtime = Runtime::new_default();
+        assert_eq!(optimize_str(&runtime, "(+ 1 2 3)"), vec![Value::Int(6)]);
+        assert_eq!(optimize_str(&runtime, "(* 2 (+ 1 2.5))"), vec![Value::Float(7.0)]);
+        assert_eq!(optimize_str(&runtime, "(< 1 (- 5 2))"), vec![Value::Bool(true)]);
+        assert_eq!(
+            optimize_str(&runtime, r#"(+ "a" 1)"#),
+            vec![Value::String("a1".to_string())]
+        );
+        assert_eq!(
+            optimize_str(&runtime, "(print (+ 1 2) x)"),
+            parse_str("(print 3 x)")
+        );
+    }
+
+    #[test]
+    fn test_does_not_fold_unsafe_arithmetic() {
+        let runtime = Runtime::new_default();
+        assert_eq!(optimize_str(&runtime, "(/ 1 0)"), parse_str("(/ 1 0)"));
+        assert_eq!(
+            optimize_str(&runtime, "(* 9223372036854775807 2)"),
+            parse_str("(* 9223372036854775807 2)")
+        );
+        assert_eq!(optimize_str(&runtime, r#"(- "a" 1)"#), parse_str(r#"(- "a" 1)"#));
+    }
+
+    #[test]
+    fn test_prunes_constant_if() {
+        let runtime = Runtime::new_default();
+        assert_eq!(
+            optimize_str(&runtime, "(if true (print 1) (print 2))"),
+            parse_str("(print 1)")
+        );
+        assert_eq!(
+            optimize_str(&runtime, "(if (> 1 2) (print 1) (print 2))"),
+            parse_str("(print 2)")
+        );
+        assert_eq!(optimize_str(&runtime, "(if false 1)"), vec![Value::Nil]);
+        assert_eq!(
+            optimize_str(&runtime, "(if x 1 2)"),
+            parse_str("(if x 1 2)")
+        );
+    }
+
+    #[test]
+    fn test_keeps_do_block_shape() {
+        let runtime = Runtime::new_default();
+        assert_eq!(
+            optimize_str(&runtime, "((+ 1 2) (if false 1) (print 1))"),
+            parse_str("((+ 1 2) () (print 1))")
+        );
+    }
+
+    #[test]
+    fn test_inlines_constant_defs() {
+        let runtime = Runtime::new_default();
+        assert_eq!(
+            optimize_str(&runtime, "((def x (+ 1 2)) (print (* x 2)))"),
+            parse_str("((def x 3) (print 6))")
+        );
+        // defined twice
+        assert_eq!(
+            optimize_str(&runtime, "((def x 1) (def x 2) (print x))"),
+            parse_str("((def x 1) (def x 2) (print x))")
+        );
+        // shadowed by a function parameter
+        assert_eq!(
+            optimize_str(&runtime, "((def x 1) (fn f (x) x) (print x))"),
+            parse_str("((def x 1) (fn f (x) x) (print x))")
+        );
+        // conditional definition
+        assert_eq!(
+            optimize_str(&runtime, "((if y (def x 1)) (print x))"),
+            parse_str("((if y (def x 1)) (print x))")
+        );
+    }
+
+    #[test]
+    fn test_skips_redefined_builtins() {
+        let runtime = Runtime::new_default();
+        assert_eq!(
+            optimize_str(&runtime, "((fn + (a b) (- a b)) (+ 5 2))"),
+            parse_str("((fn + (a b) (- a b)) (+ 5 2))")
+        );
+
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("(def if 1)").unwrap();
+        assert_eq!(
+            optimize_str(&runtime, "(if true 1 2)"),
+            parse_str("(if true 1 2)")
+        );
+    }
+
+    #[test]
+    fn test_skips_builtins_after_import() {
+        let path = std::env::temp_dir().join(format!("rssli-opt-{}.lisp", std::process::id()));
+        std::fs::write(&path, "(fn + (a b) (- a b))").unwrap();
+        let prog = format!(r#"(import "{}") (+ 5 2)"#, path.display());
+
+        let runtime = Runtime::new_default();
+        assert_eq!(optimize_str(&runtime, &prog), parse_str(&prog));
+        let mut plain = Runtime::new_default();
+        let mut optimized = Runtime::new_default();
+        optimized.set_optimize(true);
+        assert_eq!(plain.eval_string(&prog).unwrap(), Value::Int(3));
+        assert_eq!(optimized.eval_string(&prog).unwrap(), Value::Int(3));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_optimized_runtime_evaluates_the_same() {
+        let prog = r#"
+        (
+            (def n (* 2 5))
+            (fn fib (x) (if (< x 3) 1 (+ (fib (- x 1)) (fib (- x 2)))))
+            (if (== n 10) (fib n) 0)
+        )
+        "#;
+        let mut plain = Runtime::new_default();
+        let mut optimized = Runtime::new_default();
+        optimized.set_optimize(true);
+        assert_eq!(
+            plain.eval_string(prog).unwrap(),
+            optimized.eval_string(prog).unwrap()
+        );
+        assert_eq!(optimized.eval_string(prog).unwrap(), Value::Int(55));
+    }
+
+    #[test]
+    fn test_keeps_free_variables_of_functions() {
+        let runtime = Runtime::new_default();
+        assert_eq!(
+            optimize_str(&runtime, "((def x 1) (fn f () (+ x (* 2 3))) (print x))"),
+            parse_str("((def x 1) (fn f () (+ x 6)) (print 1))")
+        );
+
+        let mut runtime = Runtime::new_default();
+        runtime.set_optimize(true);
+        runtime.eval_string("((def x 1) (fn f () x))").unwrap();
+        assert_eq!(runtime.eval_string("((def x 2) (f))").unwrap(), Value::Int(2));
+    }
+}
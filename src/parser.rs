@@ -1,4 +1,7 @@
 use crate::func::FuncKind;
+use crate::object::Object;
+use crate::seq::Seq;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -14,8 +17,11 @@ pub enum Value {
     String(String),
     Symbol(String),
     List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
     Bool(bool),
     Func(FuncKind),
+    Seq(Seq),
+    Object(Object),
     Nil,
 }
 
@@ -34,8 +40,18 @@ impl Display for Value {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            Value::Map(x) => write!(
+                f,
+                "{{{}}}",
+                x.iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
             Value::Bool(x) => write!(f, "{}", x),
             Value::Func(x) => write!(f, "{:?}", x),
+            Value::Seq(x) => write!(f, "{:?}", x),
+            Value::Object(x) => write!(f, "{}", x),
             Value::Nil => write!(f, "nil"),
         }
     }
@@ -61,8 +77,11 @@ impl From<&Value> for bool {
             Value::String(s) => !s.is_empty(),
             Value::Symbol(s) => !s.is_empty(),
             Value::List(v) => !v.is_empty(),
+            Value::Map(m) => !m.is_empty(),
             Value::Bool(v) => *v,
             Value::Func(_) => true,
+            Value::Seq(_) => true,
+            Value::Object(_) => true,
             Value::Nil => false,
         }
     }
@@ -82,6 +101,7 @@ impl From<&Value> for i64 {
             Value::String(s) => s.parse::<i64>().expect("Cannot convert string to int"),
             Value::Symbol(_) => 0,
             Value::List(_) => 0,
+            Value::Map(_) => 0,
             Value::Bool(b) => {
                 if *b {
                     1
@@ -90,6 +110,8 @@ impl From<&Value> for i64 {
                 }
             }
             Value::Func(_) => 0,
+            Value::Seq(_) => 0,
+            Value::Object(_) => 0,
             Value::Nil => 0,
         }
     }
@@ -109,6 +131,7 @@ impl From<&Value> for f64 {
             Value::String(s) => s.parse::<f64>().expect("Cannot convert string to float"),
             Value::Symbol(_) => 0.0,
             Value::List(l) => l.len() as f64,
+            Value::Map(m) => m.len() as f64,
             Value::Bool(b) => {
                 if *b {
                     1.0
@@ -117,6 +140,8 @@ impl From<&Value> for f64 {
                 }
             }
             Value::Func(_) => 0.0,
+            Value::Seq(_) => 0.0,
+            Value::Object(_) => 0.0,
             Value::Nil => 0.0,
         }
     }
@@ -132,6 +157,10 @@ impl From<&Value> for Vec<Value> {
     fn from(val: &Value) -> Self {
         match val {
             Value::List(l) => l.clone(),
+            Value::Map(m) => m
+                .iter()
+                .map(|(k, v)| Value::List(vec![Value::String(k.clone()), v.clone()]))
+                .collect(),
             _ => vec![val.clone()],
         }
     }
@@ -177,6 +206,10 @@ impl Value {
         matches!(self, Value::List(_))
     }
 
+    pub fn is_map(&self) -> bool {
+        matches!(self, Value::Map(_))
+    }
+
     pub fn is_string(&self) -> bool {
         matches!(self, Value::String(_))
     }
@@ -197,6 +230,14 @@ impl Value {
         matches!(self, Value::Func(_))
     }
 
+    pub fn is_seq(&self) -> bool {
+        matches!(self, Value::Seq(_))
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, Value::Object(_))
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self {
             Value::Int(_) => "integer",
@@ -204,8 +245,11 @@ impl Value {
             Value::String(_) => "string",
             Value::Symbol(_) => "symbol",
             Value::List(_) => "list",
+            Value::Map(_) => "map",
             Value::Bool(_) => "bool",
             Value::Func(_) => "function",
+            Value::Seq(_) => "seq",
+            Value::Object(_) => "object",
             Value::Nil => "nil",
         }
     }
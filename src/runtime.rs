@@ -1,9 +1,16 @@
 use crate::{
-    corelib,
+    convert::{typed_native, TypedNative},
+    corelib::{self, Module},
     env::Environment,
+    func::NativeFn,
+    object::ObjectType,
     parser::Value,
+    sandbox::Sandbox,
+    snapshot::{copy_env, Snapshot},
+    streams::{InStream, OutStream, SharedBuffer, Streams},
 };
-use std::path::PathBuf;
+use rand::{rngs::StdRng, SeedableRng};
+use std::path::{Path, PathBuf};
 
 #[derive(Default)]
 pub struct Runtime {
@@ -24,6 +31,17 @@ impl Runtime {
         run
     }
 
+    /// Runtime with selected builtin modules and sandboxing, see `RuntimeBuilder`
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::default()
+    }
+
+    /// Enables the constant folding and dead-branch elimination pass
+    /// (see `optimizer::optimize`) for everything evaluated afterwards.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.env.set_optimize(optimize);
+    }
+
     pub fn eval_string(&mut self, prog: &str) -> anyhow::Result<Value> {
         self.env.eval_string(prog)
     }
@@ -46,12 +64,8 @@ impl Runtime {
     /// This is useful for testing functions that return lists, as `eval_string`
     /// unwraps list results to their last element (REPL behavior).
     pub fn eval_parsed(&mut self, prog: &str) -> anyhow::Result<Value> {
-        use crate::tokenizer::tokenize;
-        use crate::parser::parse_tokens;
-        
-        let tokens = tokenize(prog)?;
-        let parsed = parse_tokens(&tokens)?;
-        
+        let parsed = self.env.parse_program(prog)?;
+
         // Evaluate all expressions, return last without unwrapping
         let mut result = Value::Nil;
         for expr in &parsed {
@@ -60,11 +74,357 @@ impl Runtime {
         Ok(result)
     }
 
+    /// Registers a native function. Unlike `corelib` builtins it can be any
+    /// closure, e.g. one capturing a database handle or a counter.
+    /// Arguments are passed unevaluated, use `Environment::eval_args` to evaluate them.
+    pub fn register_fn<F>(&mut self, name: &str, func: F)
+    where
+        F: NativeFn + 'static,
+    {
+        self.env.add_native_closure(name, func, false);
+    }
+
+    /// Lets scripts call the methods of the type as `(<type>.<method> handle
+    /// args...)`, `(obj.<method> handle args...)` works for any object
+    pub fn register_object_type(&mut self, object_type: &ObjectType) {
+        self.env.register_object_type(object_type.name());
+    }
+
+    /// Registers a Rust function with typed arguments, e.g.
+    /// `runtime.register("add", |a: i64, b: i64| a + b)`.
+    /// Arguments are evaluated and converted with `FromValue`, their count
+    /// and types are checked, and the result is converted with `IntoValue`.
+    /// Trailing `Option` arguments may be omitted.
+    pub fn register<Args, F>(&mut self, name: &str, func: F)
+    where
+        F: TypedNative<Args>,
+    {
+        self.env.add_native_closure(name, typed_native(name, func), false);
+    }
+
+    /// Calls a function defined in the runtime (by `fn`, `def` or `register_fn`)
+    /// with already evaluated arguments. The result is returned as is,
+    /// single-element lists are not unwrapped.
+    ///
+    /// Special forms (`def`, `fn`, `if`, `while`, `for`, `import`, ...) are the
+    /// exception: they run in the global scope and their arguments are
+    /// expressions, like in a script. A list argument is evaluated as code, so
+    /// `call("def", &[Value::Symbol("x".into()), list])` defines `x` as the
+    /// result of calling `list`; use `set_global` to store a value as is.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> anyhow::Result<Value> {
+        let func = self.env.get_func_def(name)?;
+        self.env.apply_def(&func, args)
+    }
+
+    /// Calls a function value, e.g. a lambda returned by a script or read with `get_global`
+    pub fn call_value(&mut self, func: &Value, args: &[Value]) -> anyhow::Result<Value> {
+        match func {
+            Value::Func(kind) => self.env.apply(kind, args),
+            other => anyhow::bail!("Expected a function, got {}", other.type_name()),
+        }
+    }
+
+    /// Returns a global variable, or a function as `Value::Func`
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env.get_var_or_func(name).ok()
+    }
+
+    /// Defines or overwrites a global variable, same as `(def name value)`
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.env.vars.set(&name.to_owned(), &value);
+    }
+
+    pub fn has_function(&self, name: &str) -> bool {
+        self.env.get_func_def(name).is_ok()
+    }
+
+    /// Names of the global variables, sorted. Functions and builtin
+    /// constants like `math.pi` are not included.
+    pub fn list_globals(&self) -> Vec<String> {
+        let mut names = self.env.vars.keys();
+        names.retain(|name| !corelib::is_constant(name));
+        names.sort();
+        names
+    }
+
+    /// Copy of the current definitions that can be brought back with `restore`.
+    /// Copies every scope reachable from the runtime, so the cost grows with
+    /// the number of definitions (memo caches included).
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.env)
+    }
+
+    /// Replaces all definitions with a copy of the ones from the snapshot,
+    /// the random generator continues from its state at the snapshot.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.env = snapshot.env();
+    }
+
+    /// Independent runtime with the same definitions, e.g. one per request
+    /// on top of a preloaded library. Definitions made in either runtime
+    /// afterwards are not seen by the other. Host objects, generators and
+    /// the sandbox are shared, streams can be redirected separately. Like
+    /// `snapshot`, this copies all definitions. The random generator of the
+    /// fork is seeded from the one of this runtime, so forks of a seeded
+    /// runtime are repeatable but draw different numbers from each other.
+    /// Generators belong to the runtime that created them: once it is
+    /// dropped, they end in the fork as well.
+    pub fn fork(&self) -> Runtime {
+        let rng = StdRng::from_rng(&mut *self.env.rng().borrow_mut());
+        let mut env = copy_env(&self.env, rng);
+        env.detach_live_sources();
+        Self { env }
+    }
+
+    /// Sets the arguments returned by `sys.args`
+    pub fn set_args<I, S>(&mut self, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env.set_args(args.into_iter().map(Into::into).collect());
+    }
+
+    /// Seeds the generator of the `rnd.*` functions, like `(rnd.seed n)`,
+    /// e.g. to make tests of scripts using random numbers deterministic
+    pub fn seed(&mut self, seed: u64) {
+        self.env.seed_rng(seed);
+    }
+
+    /// Sets the path returned by `sys.script`, nil unless set
+    pub fn set_script(&mut self, path: &str) {
+        self.env.set_script(Some(path.to_owned()));
+    }
+
+    /// Redirects the output of `print`, `io.printf` and `io.readline` prompts
+    pub fn set_stdout(&mut self, out: impl OutStream + 'static) {
+        self.env.streams().set_out(Box::new(out));
+    }
+
+    /// Redirects the output of `io.eprint`
+    pub fn set_stderr(&mut self, err: impl OutStream + 'static) {
+        self.env.streams().set_err(Box::new(err));
+    }
+
+    /// Redirects the input of `io.readline`
+    pub fn set_stdin(&mut self, input: impl InStream + 'static) {
+        self.env.streams().set_input(Box::new(input));
+    }
+
+    /// Redirects stdout into a buffer and returns it, e.g. for tests:
+    /// `let out = runtime.capture_output(); ...; assert_eq!(out.contents(), "...")`
+    pub fn capture_output(&mut self) -> SharedBuffer {
+        let buffer = SharedBuffer::new();
+        self.set_stdout(buffer.clone());
+        buffer
+    }
+
+    /// Same as `capture_output` for the error stream
+    pub fn capture_errors(&mut self) -> SharedBuffer {
+        let buffer = SharedBuffer::new();
+        self.set_stderr(buffer.clone());
+        buffer
+    }
+
+    /// Goes back to the process stdout, stderr and stdin
+    pub fn reset_streams(&mut self) {
+        self.env.streams().reset();
+    }
+
     pub fn env(&self) -> &Environment {
         &self.env
     }
 }
 
+impl Drop for Runtime {
+    /// A suspended generator and the scope holding it keep each other alive
+    /// until the generator is stopped
+    fn drop(&mut self) {
+        self.env.live_sources().stop_all();
+    }
+}
+
+/**
+ * Configures a `Runtime` for embedding, e.g. to run untrusted scripts:
+ *
+ * ```
+ * use rssli::{corelib::Module, Runtime};
+ *
+ * let runtime = Runtime::builder()
+ *     .with_modules(&[Module::Core, Module::Ops, Module::List])
+ *     .deny_fs()
+ *     .build()
+ *     .unwrap();
+ * ```
+ *
+ * No builtin module is installed unless enabled.
+ */
+#[derive(Default)]
+pub struct RuntimeBuilder {
+    modules: Vec<Module>,
+    deny_fs: bool,
+    allowed_dirs: Vec<PathBuf>,
+    exec: Option<bool>,
+    env: Option<bool>,
+    args: Vec<String>,
+    seed: Option<u64>,
+    stdout: Option<Box<dyn OutStream>>,
+    stderr: Option<Box<dyn OutStream>>,
+    stdin: Option<Box<dyn InStream>>,
+    optimize: bool,
+}
+
+impl RuntimeBuilder {
+    pub fn with_module(mut self, module: Module) -> Self {
+        if !self.modules.contains(&module) {
+            self.modules.push(module);
+        }
+        self
+    }
+
+    pub fn with_modules(self, modules: &[Module]) -> Self {
+        modules.iter().fold(self, |builder, module| builder.with_module(*module))
+    }
+
+    /// Installs everything `Runtime::new_default` does
+    pub fn with_all_modules(self) -> Self {
+        self.with_modules(&Module::ALL)
+    }
+
+    pub fn without_module(mut self, module: Module) -> Self {
+        self.modules.retain(|m| *m != module);
+        self
+    }
+
+    /// Scripts cannot access files at all, `import` fails. Also denies
+    /// running programs and environment variables unless they are allowed
+    /// with `allow_exec` and `allow_env`.
+    pub fn deny_fs(mut self) -> Self {
+        self.deny_fs = true;
+        self
+    }
+
+    /// Restricts `import` (and any other file access of scripts) to the
+    /// directory and its subdirectories. Can be called for several directories.
+    /// Like `deny_fs`, also denies running programs and environment variables.
+    pub fn allow_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.allowed_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Scripts can run other processes (`sys.exec`, `sys.spawn`) even though
+    /// their file access is restricted. The programs they run are not.
+    pub fn allow_exec(mut self) -> Self {
+        self.exec = Some(true);
+        self
+    }
+
+    /// Scripts cannot run other processes, even with full file access
+    pub fn deny_exec(mut self) -> Self {
+        self.exec = Some(false);
+        self
+    }
+
+    /// Scripts can read and set environment variables (`env.*`) even though
+    /// their file access is restricted
+    pub fn allow_env(mut self) -> Self {
+        self.env = Some(true);
+        self
+    }
+
+    /// Scripts cannot read or set environment variables, even with full
+    /// file access
+    pub fn deny_env(mut self) -> Self {
+        self.env = Some(false);
+        self
+    }
+
+    /// Arguments returned by `sys.args`
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Seeds the generator of the `rnd.*` functions, by default it is
+    /// seeded from the operating system
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Redirects the output of `print`, `io.printf` and `io.readline` prompts
+    pub fn stdout(mut self, out: impl OutStream + 'static) -> Self {
+        self.stdout = Some(Box::new(out));
+        self
+    }
+
+    /// Redirects the output of `io.eprint`
+    pub fn stderr(mut self, err: impl OutStream + 'static) -> Self {
+        self.stderr = Some(Box::new(err));
+        self
+    }
+
+    /// Redirects the input of `io.readline`
+    pub fn stdin(mut self, input: impl InStream + 'static) -> Self {
+        self.stdin = Some(Box::new(input));
+        self
+    }
+
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Fails when an allowed directory does not exist
+    pub fn build(self) -> anyhow::Result<Runtime> {
+        let mut sandbox = Sandbox::default();
+        if self.deny_fs {
+            sandbox.deny_fs();
+        }
+        for dir in &self.allowed_dirs {
+            sandbox.allow_dir(dir)?;
+        }
+        match self.exec {
+            Some(true) => sandbox.allow_exec(),
+            Some(false) => sandbox.deny_exec(),
+            None => {}
+        }
+        match self.env {
+            Some(true) => sandbox.allow_env(),
+            Some(false) => sandbox.deny_env(),
+            None => {}
+        }
+
+        let streams = Streams::default();
+        if let Some(out) = self.stdout {
+            streams.set_out(out);
+        }
+        if let Some(err) = self.stderr {
+            streams.set_err(err);
+        }
+        if let Some(input) = self.stdin {
+            streams.set_input(input);
+        }
+
+        let mut runtime = Runtime::new();
+        runtime.env.set_sandbox(sandbox);
+        runtime.env.set_streams(streams);
+        runtime.env.set_args(self.args);
+        if let Some(seed) = self.seed {
+            runtime.env.seed_rng(seed);
+        }
+        runtime.env.set_optimize(self.optimize);
+        for module in self.modules {
+            module.register(&mut runtime.env);
+        }
+        Ok(runtime)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -407,6 +767,118 @@ mod tests {
         );
     }
 
+    // natives need not be `Send` without the `sync` feature
+    #[cfg(not(feature = "sync"))]
+    #[test]
+    fn test_register_fn_with_captured_state() {
+        use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+        let mut runtime = Runtime::new_default();
+        let store: Rc<RefCell<HashMap<String, Value>>> = Rc::default();
+
+        let writer = store.clone();
+        runtime.register_fn("store.put", move |args, fenv| {
+            let evl = fenv.eval_args(args)?;
+            writer.borrow_mut().insert(evl[0].as_string(), evl[1].clone());
+            Ok(Value::Nil)
+        });
+        let reader = store.clone();
+        runtime.register_fn("store.get", move |args, fenv| {
+            let key = fenv.eval(&args[0])?.as_string();
+            Ok(reader.borrow().get(&key).cloned().unwrap_or(Value::Nil))
+        });
+
+        runtime
+            .eval_string(r#"((store.put "answer" (* 6 7)) (store.put "name" "rssli"))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(store.get "answer")"#).unwrap(),
+            Value::Int(42)
+        );
+        assert_eq!(store.borrow().get("name"), Some(&Value::String("rssli".to_string())));
+    }
+
+    #[cfg(not(feature = "sync"))]
+    #[test]
+    fn test_register_fn_closure_is_first_class() {
+        use std::{cell::Cell, rc::Rc};
+
+        let mut runtime = Runtime::new_default();
+        let counter = Rc::new(Cell::new(0));
+        let inner = counter.clone();
+        runtime.register_fn("tick", move |_args, _fenv| {
+            inner.set(inner.get() + 1);
+            Ok(Value::Int(inner.get()))
+        });
+
+        let result = runtime
+            .eval_parsed("(list.map (list.seq 0 3) (fn (x) (+ x (tick))))")
+            .unwrap();
+        assert_eq!(
+            result,
+            Value::List(vec![Value::Int(1), Value::Int(3), Value::Int(5)])
+        );
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_register_fn_with_send_state() {
+        use std::{
+            collections::HashMap,
+            sync::{Arc, Mutex},
+        };
+
+        let mut runtime = Runtime::new_default();
+        let store: Arc<Mutex<HashMap<String, Value>>> = Arc::default();
+
+        let writer = store.clone();
+        runtime.register_fn("store.put", move |args, fenv| {
+            let evl = fenv.eval_args(args)?;
+            writer.lock().unwrap().insert(evl[0].as_string(), evl[1].clone());
+            Ok(Value::Nil)
+        });
+        let reader = store.clone();
+        runtime.register_fn("store.get", move |args, fenv| {
+            let key = fenv.eval(&args[0])?.as_string();
+            Ok(reader.lock().unwrap().get(&key).cloned().unwrap_or(Value::Nil))
+        });
+
+        runtime
+            .eval_string(r#"((store.put "answer" (* 6 7)) (store.put "name" "rssli"))"#)
+            .unwrap();
+        assert_eq!(
+            runtime.eval_string(r#"(store.get "answer")"#).unwrap(),
+            Value::Int(42)
+        );
+        assert_eq!(store.lock().unwrap().get("name"), Some(&Value::String("rssli".to_string())));
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_register_fn_send_closure_is_first_class() {
+        use std::sync::{
+            atomic::{AtomicI64, Ordering},
+            Arc,
+        };
+
+        let mut runtime = Runtime::new_default();
+        let counter = Arc::new(AtomicI64::new(0));
+        let inner = counter.clone();
+        runtime.register_fn("tick", move |_args, _fenv| {
+            Ok(Value::Int(inner.fetch_add(1, Ordering::Relaxed) + 1))
+        });
+
+        let result = runtime
+            .eval_parsed("(list.map (list.seq 0 3) (fn (x) (+ x (tick))))")
+            .unwrap();
+        assert_eq!(
+            result,
+            Value::List(vec![Value::Int(1), Value::Int(3), Value::Int(5)])
+        );
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+    }
+
     #[test]
     fn test_modulo_operator() {
         let mut runtime = Runtime::new_default();
@@ -417,4 +889,287 @@ mod tests {
             Value::Float(1.5)
         );
     }
+
+    #[test]
+    fn test_call_script_functions() {
+        let mut runtime = Runtime::new_default();
+        runtime
+            .eval_string(
+                r#"
+            (
+                (def greeting "hello")
+                (fn handle (name) (+ greeting " " name))
+                (def double (fn (x) (* x 2)))
+            )
+            "#,
+            )
+            .unwrap();
+
+        assert!(runtime.has_function("handle"));
+        assert!(runtime.has_function("double"));
+        assert!(!runtime.has_function("missing"));
+        for name in ["a", "b"] {
+            assert_eq!(
+                runtime.call("handle", &[Value::String(name.to_string())]).unwrap(),
+                Value::String(format!("hello {}", name))
+            );
+        }
+
+        let double = runtime.get_global("double").unwrap();
+        assert_eq!(runtime.call_value(&double, &[Value::Int(21)]).unwrap(), Value::Int(42));
+        assert!(runtime.call_value(&Value::Int(1), &[]).is_err());
+        assert!(runtime.call("missing", &[]).is_err());
+    }
+
+    #[test]
+    fn test_call_native_with_values() {
+        let mut runtime = Runtime::new_default();
+        let pairs = Value::List(vec![
+            Value::List(vec![Value::Int(1), Value::Int(2)]),
+            Value::List(vec![Value::Int(3), Value::Int(4)]),
+        ]);
+        let inc = runtime.eval_string("(fn (x) (+ x 1))").unwrap();
+        let list = Value::List(vec![Value::Int(1), Value::Int(2)]);
+
+        assert_eq!(runtime.call("list.len", &[pairs]).unwrap(), Value::Int(2));
+        assert_eq!(
+            runtime.call("list.map", &[list, inc]).unwrap(),
+            Value::List(vec![Value::Int(2), Value::Int(3)])
+        );
+
+        // special forms get their arguments as expressions
+        let sym = |name: &str| Value::Symbol(name.to_string());
+        runtime.call("def", &[sym("zz"), Value::Int(3)]).unwrap();
+        assert_eq!(runtime.get_global("zz"), Some(Value::Int(3)));
+        let body = Value::List(vec![sym("*"), sym("x"), Value::Int(3)]);
+        runtime
+            .call("fn", &[sym("triple"), Value::List(vec![sym("x")]), body])
+            .unwrap();
+        assert_eq!(runtime.call("triple", &[Value::Int(2)]).unwrap(), Value::Int(6));
+
+        // a list argument of a special form is code, not data
+        let sum = Value::List(vec![sym("+"), Value::Int(1), Value::Int(2)]);
+        runtime.call("def", &[sym("zz"), sum.clone()]).unwrap();
+        assert_eq!(runtime.get_global("zz"), Some(Value::Int(3)));
+        runtime.set_global("zz", sum.clone());
+        assert_eq!(runtime.get_global("zz"), Some(sum));
+        let cond = Value::List(vec![sym(">"), sym("zz2"), Value::Int(1)]);
+        runtime.set_global("zz2", Value::Int(2));
+        assert_eq!(
+            runtime.call("if", &[cond, Value::Int(10), Value::Int(20)]).unwrap(),
+            Value::Int(10)
+        );
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("limit", Value::Int(10));
+        runtime.eval_string("(def total (* limit 2))").unwrap();
+
+        assert_eq!(runtime.get_global("total"), Some(Value::Int(20)));
+        assert_eq!(runtime.get_global("missing"), None);
+        assert_eq!(runtime.list_globals(), vec!["limit".to_string(), "total".to_string()]);
+    }
+
+    #[test]
+    fn test_fork_isolates_definitions() {
+        let mut base = Runtime::new_default();
+        base.eval_string(
+            r#"(
+                (def greeting "hi")
+                (def count 0)
+                (fn greet () greeting)
+                (fn bump () (def count (+ count 1)))
+                (fn-memo fib (x) (if (< x 3) 1 (+ (fib (- x 1)) (fib (- x 2)))))
+            )"#,
+        )
+        .unwrap();
+
+        let mut fork = base.fork();
+        fork.eval_string(r#"((def greeting "hey") (bump) (bump) (def extra 1))"#).unwrap();
+        assert_eq!(fork.eval_string(r#"(greet)"#).unwrap(), Value::String("hey".into()));
+        assert_eq!(fork.get_global("count"), Some(Value::Int(2)));
+        assert_eq!(fork.eval_string("(fib 30)").unwrap(), Value::Int(832040));
+
+        assert_eq!(base.eval_string(r#"(greet)"#).unwrap(), Value::String("hi".into()));
+        assert_eq!(base.get_global("count"), Some(Value::Int(0)));
+        assert_eq!(base.get_global("extra"), None);
+
+        // the memo cache is copied as well
+        let memo_size = |runtime: &mut Runtime| {
+            match runtime.eval_parsed("(memo.stats fib)").unwrap() {
+                Value::List(stats) => stats[2].clone(),
+                other => panic!("unexpected stats {:?}", other),
+            }
+        };
+        let size = |n| Value::List(vec![Value::String("size".into()), Value::Int(n)]);
+        assert_eq!(memo_size(&mut base), size(0));
+        assert_eq!(memo_size(&mut fork), size(30));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("((def x 1) (fn get-x () x))").unwrap();
+        let snapshot = runtime.snapshot();
+
+        for _ in 0..2 {
+            runtime.eval_string("((def x 2) (def y 3) (fn get-y () y))").unwrap();
+            assert_eq!(runtime.eval_string("(get-x)").unwrap(), Value::Int(2));
+
+            runtime.restore(&snapshot);
+            assert_eq!(runtime.eval_string("(get-x)").unwrap(), Value::Int(1));
+            assert_eq!(runtime.get_global("y"), None);
+            assert!(!runtime.has_function("get-y"));
+        }
+    }
+
+    #[test]
+    fn test_fork_redirects_streams_separately() {
+        let mut base = Runtime::new_default();
+        let base_out = base.capture_output();
+        base.eval_string(r#"(fn hello (name) (print "hello" name))"#).unwrap();
+
+        let mut fork = base.fork();
+        fork.eval_string(r#"(hello "fork")"#).unwrap();
+        let fork_out = fork.capture_output();
+        fork.eval_string(r#"(hello "again")"#).unwrap();
+        base.eval_string(r#"(hello "base")"#).unwrap();
+
+        assert_eq!(base_out.contents(), "hello fork\nhello base\n");
+        assert_eq!(fork_out.contents(), "hello again\n");
+    }
+
+    #[test]
+    fn test_builder_selected_modules() {
+        let mut runtime = Runtime::builder()
+            .with_modules(&[Module::Core, Module::Ops])
+            .build()
+            .unwrap();
+
+        assert_eq!(runtime.eval_string("((def x 2) (+ x 1))").unwrap(), Value::Int(3));
+        let err = runtime.eval_string("(print x)").unwrap_err();
+        assert_eq!(err.to_string(), "Undeclared function: print");
+        assert!(runtime.eval_string(r#"(import "lib.lsp")"#).is_err());
+
+        let runtime = Runtime::builder()
+            .with_all_modules()
+            .without_module(Module::System)
+            .build()
+            .unwrap();
+        assert!(!runtime.has_function("exit"));
+        assert!(runtime.has_function("print"));
+        assert!(runtime.has_function("sys.exec"));
+
+        let runtime = Runtime::builder().with_module(Module::System).build().unwrap();
+        assert!(runtime.has_function("import"));
+        assert!(!runtime.has_function("sys.exec"));
+        assert!(!runtime.has_function("env.get"));
+    }
+
+    #[test]
+    fn test_builder_redirects_streams() {
+        let out = SharedBuffer::new();
+        let mut runtime = Runtime::builder()
+            .with_modules(&[Module::Core, Module::Io, Module::Ops])
+            .stdout(out.clone())
+            .stdin(std::io::Cursor::new("alice\nbob\n"))
+            .build()
+            .unwrap();
+
+        runtime
+            .eval_string(
+                r#"
+            (
+                (print "hello" (io.readline "name?"))
+                (io.printf "and %v" (io.readline))
+            )
+            "#,
+            )
+            .unwrap();
+        assert_eq!(out.contents(), "name? hello alice\nand bob");
+    }
+
+    #[test]
+    fn test_builder_filesystem_sandbox() {
+        let root = std::env::temp_dir().join(format!("rssli-sandbox-{}", std::process::id()));
+        let allowed = root.join("allowed");
+        let other = root.join("other");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(allowed.join("lib.lsp"), "(def answer 42)").unwrap();
+        std::fs::write(other.join("secret.lsp"), "(def answer 0)").unwrap();
+
+        let mut runtime = Runtime::builder()
+            .with_all_modules()
+            .allow_dir(&allowed)
+            .build()
+            .unwrap();
+        let import = |dir: &PathBuf, file: &str| {
+            format!("(import \"{}\")", dir.join(file).display())
+        };
+        runtime.eval_string(&import(&allowed, "lib.lsp")).unwrap();
+        assert_eq!(runtime.get_global("answer"), Some(Value::Int(42)));
+        let err = runtime.eval_string(&import(&other, "secret.lsp")).unwrap_err();
+        assert!(err.to_string().starts_with("Access outside of the allowed directories"));
+        let err = runtime.eval_string(&import(&allowed, "../other/secret.lsp")).unwrap_err();
+        assert!(err.to_string().starts_with("Access outside of the allowed directories"));
+
+        let mut runtime = Runtime::builder().with_all_modules().deny_fs().build().unwrap();
+        let err = runtime.eval_string(&import(&allowed, "lib.lsp")).unwrap_err();
+        assert!(err.to_string().ends_with("filesystem access is denied"));
+
+        assert!(Runtime::builder().allow_dir(root.join("missing")).build().is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_runtimes_on_multiple_threads() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Runtime>();
+        assert_send::<Value>();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let mut runtime = Runtime::new_default();
+                    runtime.set_global("n", Value::Int(i));
+                    runtime
+                        .eval_string(
+                            r#"
+                        (
+                            (fn-memo fib (x) (if (< x 3) 1 (+ (fib (- x 1)) (fib (- x 2)))))
+                            (def squares (seq.map (seq.range 0 n) (fn (x) (* x x))))
+                            (+ (fib (+ n 20)) (list.len (seq.collect squares)))
+                        )
+                        "#,
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Value::Int(6765),
+                Value::Int(10947),
+                Value::Int(17713),
+                Value::Int(28660)
+            ]
+        );
+
+        // a loaded runtime can be handed over to a worker thread
+        let mut runtime = Runtime::new_default();
+        runtime.eval_string("(fn handle (x) (* x 2))").unwrap();
+        let runtime = std::thread::spawn(move || {
+            assert_eq!(runtime.call("handle", &[Value::Int(21)]).unwrap(), Value::Int(42));
+            runtime
+        })
+        .join()
+        .unwrap();
+        assert!(runtime.has_function("handle"));
+    }
 }
@@ -0,0 +1,152 @@
+use std::path::{Component, Path, PathBuf};
+
+/**
+ * Limits what scripts may touch outside of the interpreter.
+ * The default sandbox allows everything, embedders running untrusted
+ * scripts deny filesystem access or restrict it to a few directories (see
+ * `RuntimeBuilder`). Programs run by scripts are not bound by the sandbox,
+ * so restricting file access also denies running processes and environment
+ * variables, unless they are allowed explicitly with `allow_exec` and
+ * `allow_env`.
+ */
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    fs: bool,
+    allowed_dirs: Option<Vec<PathBuf>>,
+    /// Allowed or denied explicitly, otherwise follows file access
+    exec: Option<bool>,
+    env: Option<bool>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            fs: true,
+            allowed_dirs: None,
+            exec: None,
+            env: None,
+        }
+    }
+}
+
+impl Sandbox {
+    /// Denies all file access from scripts, including `import`
+    pub fn deny_fs(&mut self) {
+        self.fs = false;
+    }
+
+    /// Restricts file access to the directory and its subdirectories.
+    /// Can be called repeatedly, any of the directories is accepted.
+    pub fn allow_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let canonical = dir
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("Failed to canonicalize path {}: {}", dir.display(), e))?;
+        self.allowed_dirs
+            .get_or_insert_with(Vec::new)
+            .push(canonical);
+        Ok(())
+    }
+
+    /// Allows `sys.exec` and `sys.spawn` even though file access is
+    /// restricted. Allowed directories do not apply to other processes.
+    pub fn allow_exec(&mut self) {
+        self.exec = Some(true);
+    }
+
+    /// Denies `sys.exec` and `sys.spawn`
+    pub fn deny_exec(&mut self) {
+        self.exec = Some(false);
+    }
+
+    /// Allows reading and changing environment variables (`env.*`) even
+    /// though file access is restricted
+    pub fn allow_env(&mut self) {
+        self.env = Some(true);
+    }
+
+    /// Denies reading and changing environment variables (`env.*`)
+    pub fn deny_env(&mut self) {
+        self.env = Some(false);
+    }
+
+    pub fn fs_allowed(&self) -> bool {
+        self.fs
+    }
+
+    /// True when scripts cannot access every file
+    fn fs_restricted(&self) -> bool {
+        !self.fs || self.allowed_dirs.is_some()
+    }
+
+    pub fn exec_allowed(&self) -> bool {
+        self.exec.unwrap_or(!self.fs_restricted())
+    }
+
+    pub fn env_allowed(&self) -> bool {
+        self.env.unwrap_or(!self.fs_restricted())
+    }
+
+    pub fn check_exec(&self, program: &str) -> anyhow::Result<()> {
+        if !self.exec_allowed() {
+            anyhow::bail!("Process execution is denied: {}", program);
+        }
+        Ok(())
+    }
+
+    pub fn check_env(&self, name: &str) -> anyhow::Result<()> {
+        if !self.env_allowed() {
+            anyhow::bail!("Environment access is denied: {}", name);
+        }
+        Ok(())
+    }
+
+    /// Checks that scripts may access the path, which must be canonical
+    pub fn check_path(&self, path: &Path) -> anyhow::Result<()> {
+        if !self.fs {
+            anyhow::bail!("Filesystem access is denied: {}", path.display());
+        }
+        match &self.allowed_dirs {
+            Some(dirs) if !dirs.iter().any(|dir| path.starts_with(dir)) => {
+                anyhow::bail!(
+                    "Access outside of the allowed directories: {}",
+                    path.display()
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks an absolute path that may not exist yet, e.g. a file about to
+    /// be written. Symlinks in the existing part of the path are resolved.
+    pub fn check_access(&self, path: &Path) -> anyhow::Result<()> {
+        if !self.fs {
+            anyhow::bail!("Filesystem access is denied: {}", path.display());
+        }
+        if self.allowed_dirs.is_none() {
+            return Ok(());
+        }
+        self.check_path(&canonicalize_existing(path)?)
+    }
+}
+
+/// Canonicalizes the longest existing ancestor and appends the rest of the
+/// path, whose `..` can only refer to directories created later on
+fn canonicalize_existing(path: &Path) -> anyhow::Result<PathBuf> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(path);
+    let mut canonical = existing
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Failed to canonicalize path {}: {}", path.display(), e))?;
+    for component in path.strip_prefix(existing)?.components() {
+        match component {
+            Component::ParentDir => {
+                canonical.pop();
+            }
+            Component::CurDir => {}
+            other => canonical.push(other),
+        }
+    }
+    Ok(canonical)
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::{
+    env::Environment,
+    func::FuncKind,
+    parser::Value,
+    sync::{Lock, MaybeSendSync, Shared, Weak},
+};
+
+/**
+ * Producer of values for sequences backed by external state
+ * (generators, streams, ...). Unlike the other kinds of sequences,
+ * sources are consumed by iteration. Sources lock their own state and must
+ * not keep it locked while Lisp code runs, which may read the same source.
+ */
+pub trait SeqSource: MaybeSendSync {
+    fn next(&self, env: &mut Environment) -> anyhow::Result<Option<Value>>;
+
+    /// Releases what the source holds, for sources created with
+    /// `Seq::from_stoppable_source` when their runtime is dropped
+    fn stop(&self) {}
+}
+
+/// Sources to stop when their runtime is dropped. They are held weakly, so
+/// dropping the last sequence of a source still releases it right away.
+#[derive(Default)]
+pub(crate) struct LiveSources(Lock<Vec<Weak<dyn SeqSource>>>);
+
+impl LiveSources {
+    fn add(&self, source: &Shared<dyn SeqSource>) {
+        let mut sources = self.0.borrow_mut();
+        sources.retain(|source| source.strong_count() > 0);
+        sources.push(Shared::downgrade(source));
+    }
+
+    pub(crate) fn stop_all(&self) {
+        let sources: Vec<_> = self.0.take().iter().filter_map(Weak::upgrade).collect();
+        for source in sources {
+            source.stop();
+        }
+    }
+}
+
+/// Lazy sequence. Cloning is cheap, every `iter()` starts from the beginning
+/// (except for sources, which are shared).
+#[derive(Clone)]
+pub struct Seq(Shared<SeqKind>);
+
+pub enum SeqKind {
+    Range {
+        start: i64,
+        end: Option<i64>,
+        step: i64,
+    },
+    List(Vec<Value>),
+    Map(Seq, FuncKind),
+    Filter(Seq, FuncKind),
+    Take(Seq, usize),
+    TakeWhile(Seq, FuncKind),
+    Source(Shared<dyn SeqSource>),
+}
+
+impl Seq {
+    pub fn new(kind: SeqKind) -> Self {
+        Self(Shared::new(kind))
+    }
+
+    pub fn range(start: i64, end: Option<i64>, step: i64) -> anyhow::Result<Self> {
+        if step == 0 {
+            anyhow::bail!("Sequence step cannot be zero");
+        }
+        Ok(Self::new(SeqKind::Range { start, end, step }))
+    }
+
+    pub fn from_source(source: impl SeqSource + 'static) -> Self {
+        Self::new(SeqKind::Source(Shared::new(source)))
+    }
+
+    /// Like `from_source`, the source is stopped when the runtime of `env`
+    /// is dropped
+    pub(crate) fn from_stoppable_source(
+        source: impl SeqSource + 'static,
+        env: &Environment,
+    ) -> Self {
+        let source: Shared<dyn SeqSource> = Shared::new(source);
+        env.live_sources().add(&source);
+        Self::new(SeqKind::Source(source))
+    }
+
+    pub fn kind(&self) -> &SeqKind {
+        &self.0
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind() {
+            SeqKind::Range { .. } => "range",
+            SeqKind::List(_) => "list",
+            SeqKind::Map(..) => "map",
+            SeqKind::Filter(..) => "filter",
+            SeqKind::Take(..) => "take",
+            SeqKind::TakeWhile(..) => "take-while",
+            SeqKind::Source(_) => "source",
+        }
+    }
+
+    /// True when the sequence never ends regardless of the functions applied to it
+    pub fn is_infinite(&self) -> bool {
+        match self.kind() {
+            SeqKind::Range { end, .. } => end.is_none(),
+            SeqKind::Map(inner, _) | SeqKind::Filter(inner, _) => inner.is_infinite(),
+            _ => false,
+        }
+    }
+
+    pub fn iter(&self) -> SeqIter {
+        match self.kind() {
+            SeqKind::Range { start, end, step } => SeqIter::Range {
+                next: Some(*start),
+                end: *end,
+                step: *step,
+            },
+            SeqKind::List(_) => SeqIter::List(self.clone(), 0),
+            SeqKind::Map(inner, func) => SeqIter::Map(Box::new(inner.iter()), func.clone()),
+            SeqKind::Filter(inner, func) => SeqIter::Filter(Box::new(inner.iter()), func.clone()),
+            SeqKind::Take(inner, n) => SeqIter::Take(Box::new(inner.iter()), *n),
+            SeqKind::TakeWhile(inner, func) => {
+                SeqIter::TakeWhile(Box::new(inner.iter()), func.clone(), false)
+            }
+            SeqKind::Source(source) => SeqIter::Source(source.clone()),
+        }
+    }
+
+    pub fn collect(&self, env: &mut Environment) -> anyhow::Result<Vec<Value>> {
+        if self.is_infinite() {
+            anyhow::bail!("Cannot collect an infinite sequence, use seq.take first");
+        }
+        let mut it = self.iter();
+        let mut values = Vec::new();
+        while let Some(value) = it.next(env)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+impl PartialEq for Seq {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for Seq {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Seq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(seq {})", self.kind_name())
+    }
+}
+
+pub enum SeqIter {
+    Range {
+        next: Option<i64>,
+        end: Option<i64>,
+        step: i64,
+    },
+    List(Seq, usize),
+    Map(Box<SeqIter>, FuncKind),
+    Filter(Box<SeqIter>, FuncKind),
+    Take(Box<SeqIter>, usize),
+    TakeWhile(Box<SeqIter>, FuncKind, bool),
+    Source(Shared<dyn SeqSource>),
+}
+
+impl SeqIter {
+    pub fn next(&mut self, env: &mut Environment) -> anyhow::Result<Option<Value>> {
+        match self {
+            SeqIter::Range { next, end, step } => {
+                let Some(current) = *next else {
+                    return Ok(None);
+                };
+                let finished = match end {
+                    Some(end) if *step > 0 => current >= *end,
+                    Some(end) => current <= *end,
+                    None => false,
+                };
+                if finished {
+                    *next = None;
+                    return Ok(None);
+                }
+                *next = current.checked_add(*step);
+                Ok(Some(Value::Int(current)))
+            }
+            SeqIter::List(seq, pos) => {
+                let SeqKind::List(items) = seq.kind() else {
+                    return Ok(None);
+                };
+                let item = items.get(*pos).cloned();
+                *pos += 1;
+                Ok(item)
+            }
+            SeqIter::Map(inner, func) => match inner.next(env)? {
+                Some(value) => Ok(Some(call(env, func, value)?)),
+                None => Ok(None),
+            },
+            SeqIter::Filter(inner, func) => {
+                while let Some(value) = inner.next(env)? {
+                    if call(env, func, value.clone())?.as_bool() {
+                        return Ok(Some(value));
+                    }
+                }
+                Ok(None)
+            }
+            SeqIter::Take(inner, remaining) => {
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                *remaining -= 1;
+                inner.next(env)
+            }
+            SeqIter::TakeWhile(inner, func, done) => {
+                if *done {
+                    return Ok(None);
+                }
+                match inner.next(env)? {
+                    Some(value) if call(env, func, value.clone())?.as_bool() => Ok(Some(value)),
+                    _ => {
+                        *done = true;
+                        Ok(None)
+                    }
+                }
+            }
+            SeqIter::Source(source) => source.next(env),
+        }
+    }
+}
+
+fn call(env: &mut Environment, func: &FuncKind, value: Value) -> anyhow::Result<Value> {
+    let call_list = Value::List(vec![Value::Func(func.clone()), value]);
+    env.eval(&call_list)
+}
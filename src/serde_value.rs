@@ -0,0 +1,658 @@
+//! Conversion between `Value` and the serde data model, enabled by the
+//! `serde` feature. Rust structs become maps, sequences and tuples become
+//! lists and `None`/unit become `nil`. Enums use the externally tagged
+//! representation: a unit variant is its name, other variants are a map
+//! with the variant name as the only key.
+
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{self, value::Error, DeserializeOwned, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Error as _, Serialize},
+    Deserialize, Deserializer, Serializer,
+};
+
+use crate::parser::Value;
+
+/// Converts any serializable Rust value into an interpreter value
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<Value> {
+    value
+        .serialize(ValueSerializer)
+        .map_err(|e| anyhow::anyhow!("Cannot convert to value: {}", e))
+}
+
+/// Converts an interpreter value into any deserializable Rust value
+pub fn from_value<T: DeserializeOwned>(value: Value) -> anyhow::Result<T> {
+    T::deserialize(value).map_err(|e| anyhow::anyhow!("Cannot convert from value: {}", e))
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Int(x) => serializer.serialize_i64(*x),
+            Value::Float(x) => serializer.serialize_f64(*x),
+            Value::String(x) | Value::Symbol(x) => serializer.serialize_str(x),
+            Value::List(items) => serializer.collect_seq(items),
+            Value::Map(items) => serializer.collect_map(items),
+            Value::Bool(x) => serializer.serialize_bool(*x),
+            Value::Nil => serializer.serialize_unit(),
+            Value::Func(_) | Value::Seq(_) | Value::Object(_) => Err(S::Error::custom(format!(
+                "cannot serialize a {}",
+                self.type_name()
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        i64::try_from(v)
+            .map(Value::Int)
+            .map_err(|_| E::custom(format!("integer {} is out of range", v)))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Int(*b as i64)).collect(),
+        ))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut items = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<Value, Value>()? {
+            items.insert(key.as_string(), value);
+        }
+        Ok(Value::Map(items))
+    }
+}
+
+// Value -> Rust
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Int(x) => visitor.visit_i64(x),
+            Value::Float(x) => visitor.visit_f64(x),
+            Value::String(x) | Value::Symbol(x) => visitor.visit_string(x),
+            Value::List(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()))
+            }
+            Value::Map(items) => {
+                visitor.visit_map(de::value::MapDeserializer::new(items.into_iter()))
+            }
+            Value::Bool(x) => visitor.visit_bool(x),
+            Value::Nil => visitor.visit_unit(),
+            Value::Func(_) | Value::Seq(_) | Value::Object(_) => Err(Error::custom(format!(
+                "cannot deserialize a {}",
+                self.type_name()
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Nil => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::String(variant) | Value::Symbol(variant) => {
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Value::Map(items) if items.len() == 1 => {
+                let (variant, value) = items.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            other => Err(Error::custom(format!(
+                "expected an enum variant name or a map with a single key, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Value), Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self {
+            Value::Nil => Ok(()),
+            other => Err(Error::custom(format!(
+                "expected nil, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+// Rust -> Value
+
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        i64::try_from(v)
+            .map(Value::Int)
+            .map_err(|_| Error::custom(format!("integer {} is out of range", v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(Value::Float(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Int(*b as i64)).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let value = value.serialize(ValueSerializer)?;
+        Ok(Value::Map(BTreeMap::from([(variant.to_owned(), value)])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            variant: None,
+            items: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            items: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+/// Wraps the value of a tuple or struct enum variant as `{variant: value}`
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Map(BTreeMap::from([(variant.to_owned(), value)])),
+        None => value,
+    }
+}
+
+struct ListSerializer {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        Ok(wrap_variant(self.variant, Value::List(self.items)))
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    items: BTreeMap<String, Value>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = match key.serialize(ValueSerializer)? {
+            Value::List(_) | Value::Map(_) => {
+                return Err(Error::custom("map keys must be strings, numbers or bools"))
+            }
+            key => Some(key.as_string()),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::custom("map value serialized before its key"))?;
+        self.items.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(wrap_variant(self.variant, Value::Map(self.items)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.items
+            .insert(key.to_owned(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(wrap_variant(self.variant, Value::Map(self.items)))
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::runtime::Runtime;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Admin,
+        Guest { until: i64 },
+        Custom(String),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+        email: Option<String>,
+        tags: Vec<String>,
+        roles: Vec<Role>,
+        scores: HashMap<String, f64>,
+    }
+
+    fn user() -> User {
+        User {
+            name: "ada".to_string(),
+            age: 36,
+            email: None,
+            tags: vec!["math".to_string()],
+            roles: vec![
+                Role::Admin,
+                Role::Guest { until: 10 },
+                Role::Custom("x".to_string()),
+            ],
+            scores: HashMap::from([("chess".to_string(), 9.5)]),
+        }
+    }
+
+    #[test]
+    fn test_to_value_struct() {
+        let value = to_value(&user()).unwrap();
+        let Value::Map(items) = &value else {
+            panic!("expected a map, got {:?}", value);
+        };
+        assert_eq!(items["name"], Value::String("ada".to_string()));
+        assert_eq!(items["age"], Value::Int(36));
+        assert_eq!(items["email"], Value::Nil);
+        assert_eq!(
+            value.to_string(),
+            "{age: 36, email: nil, name: ada, roles: (Admin, {Guest: {until: 10}}, {Custom: x}), \
+             scores: {chess: 9.5}, tags: (math)}"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_through_script() {
+        let mut runtime = Runtime::new_default();
+        runtime.set_global("user", to_value(&user()).unwrap());
+        let updated = runtime
+            .eval_string(r#"(map.set (map.set user "age" 37) "email" "ada@example.com")"#)
+            .unwrap();
+
+        let expected = User {
+            age: 37,
+            email: Some("ada@example.com".to_string()),
+            ..user()
+        };
+        assert_eq!(from_value::<User>(updated).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_from_value_errors() {
+        let err = from_value::<User>(Value::Int(1)).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Cannot convert from value: invalid type"));
+        assert!(from_value::<u8>(Value::Int(300)).is_err());
+        assert!(to_value(&u64::MAX).is_err());
+
+        let mut runtime = Runtime::new_default();
+        let func = runtime.eval_string("(fn (x) x)").unwrap();
+        assert!(from_value::<String>(func).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+
+use crate::{
+    env::Environment,
+    func::{FuncDef, FuncKind},
+    memo::Memo,
+    parser::Value,
+    seq::{Seq, SeqKind},
+    streams::Streams,
+    sync::{Lock, Shared},
+    utils::HierCellMapWrap,
+};
+
+type Scope<V> = HierCellMapWrap<String, V>;
+
+/**
+ * Frozen copy of a runtime's environment taken by `Runtime::snapshot`.
+ * Definitions made after the snapshot was taken are discarded by
+ * `Runtime::restore`, and it can be restored any number of times.
+ * Taking and restoring a snapshot both copy the environment (see `copy_env`).
+ */
+pub struct Snapshot {
+    env: Environment,
+}
+
+impl Snapshot {
+    pub(crate) fn new(env: &Environment) -> Self {
+        Self {
+            env: copy_env(env, env.rng().borrow().clone()),
+        }
+    }
+
+    pub(crate) fn env(&self) -> Environment {
+        copy_env(&self.env, self.env.rng().borrow().clone())
+    }
+}
+
+/**
+ * Copies the scopes reachable from the environment, including the ones
+ * captured by closures, so that definitions in the copy are not seen by the
+ * original and the other way around. Closures are rebound to the copied
+ * scopes. The copy is eager, not copy-on-write: every value stored in a
+ * scope is cloned, closure parameter lists and bodies and memoized results
+ * included, so the cost grows with the size of the definitions. Natives,
+ * host objects and generators are shared. The copy gets its own random
+ * generator with the given state.
+ */
+pub(crate) fn copy_env(env: &Environment, rng: StdRng) -> Environment {
+    let mut copier = Copier {
+        funcs: HashMap::new(),
+        vars: HashMap::new(),
+        memos: HashMap::new(),
+        streams: Shared::new(env.streams().fork()),
+        rng: Shared::new(Lock::new(rng)),
+    };
+    copier.env(env)
+}
+
+struct Copier {
+    funcs: HashMap<usize, Scope<FuncDef>>,
+    vars: HashMap<usize, Scope<Value>>,
+    memos: HashMap<usize, Shared<Memo>>,
+    streams: Shared<Streams>,
+    rng: Shared<Lock<StdRng>>,
+}
+
+impl Copier {
+    fn env(&mut self, env: &Environment) -> Environment {
+        let funcs = self.scope(env.funcs(), |c| &mut c.funcs, Self::func_def);
+        let vars = self.scope(env.vars(), |c| &mut c.vars, Self::value);
+        env.with_scopes(funcs, vars, self.streams.clone(), self.rng.clone())
+    }
+
+    fn scope<V: Clone>(
+        &mut self,
+        scope: &Scope<V>,
+        copies: fn(&mut Self) -> &mut HashMap<usize, Scope<V>>,
+        copy_value: fn(&mut Self, &V) -> V,
+    ) -> Scope<V> {
+        if let Some(copy) = copies(self).get(&scope.id()) {
+            return copy.clone();
+        }
+        let mut copy = match scope.parent() {
+            Some(parent) => self.scope(&parent, copies, copy_value).new_child(),
+            None => Scope::new_root(),
+        };
+        // registered before the values are copied, closures stored in a
+        // scope usually capture that scope
+        copies(self).insert(scope.id(), copy.clone());
+        for (name, value) in scope.data() {
+            let value = copy_value(self, &value);
+            copy.set(&name, &value);
+        }
+        copy
+    }
+
+    fn func_def(&mut self, def: &FuncDef) -> FuncDef {
+        FuncDef {
+            metadata: def.metadata.clone(),
+            kind: self.func(&def.kind),
+        }
+    }
+
+    fn func(&mut self, func: &FuncKind) -> FuncKind {
+        match func {
+            FuncKind::Native(_) => func.clone(),
+            FuncKind::Closure(func_val, env) => FuncKind::Closure(func_val.clone(), self.env(env)),
+            FuncKind::Memoized(memo) => {
+                let id = Shared::as_ptr(memo) as usize;
+                if let Some(copy) = self.memos.get(&id) {
+                    return FuncKind::Memoized(copy.clone());
+                }
+                let inner = self.func(memo.func());
+                let copy = Shared::new(memo.with_func(inner));
+                self.memos.insert(id, copy.clone());
+                FuncKind::Memoized(copy)
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value) -> Value {
+        match value {
+            Value::List(items) => Value::List(items.iter().map(|item| self.value(item)).collect()),
+            Value::Map(entries) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.value(value)))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+            Value::Func(func) => Value::Func(self.func(func)),
+            Value::Seq(seq) => Value::Seq(self.seq(seq)),
+            _ => value.clone(),
+        }
+    }
+
+    fn seq(&mut self, seq: &Seq) -> Seq {
+        let kind = match seq.kind() {
+            SeqKind::List(items) => {
+                SeqKind::List(items.iter().map(|item| self.value(item)).collect())
+            }
+            SeqKind::Map(inner, func) => SeqKind::Map(self.seq(inner), self.func(func)),
+            SeqKind::Filter(inner, func) => SeqKind::Filter(self.seq(inner), self.func(func)),
+            SeqKind::Take(inner, count) => SeqKind::Take(self.seq(inner), *count),
+            SeqKind::TakeWhile(inner, func) => SeqKind::TakeWhile(self.seq(inner), self.func(func)),
+            // ranges are immutable and generators are consumed by iteration
+            SeqKind::Range { .. } | SeqKind::Source(_) => return seq.clone(),
+        };
+        Seq::new(kind)
+    }
+}
@@ -0,0 +1,147 @@
+use std::io::{self, BufRead, Read, Write};
+
+use crate::sync::{Lock, MaybeSend, Shared};
+
+/// Writer usable as an output stream (`Send` with the `sync` feature)
+pub trait OutStream: Write + MaybeSend {}
+
+impl<W: Write + MaybeSend> OutStream for W {}
+
+/// Reader usable as an input stream (`Send` with the `sync` feature)
+pub trait InStream: BufRead + MaybeSend {}
+
+impl<R: BufRead + MaybeSend> InStream for R {}
+
+// streams are shared with forked runtimes (see `Streams::fork`)
+type Slot<S> = Lock<Option<Shared<Lock<Box<S>>>>>;
+
+/**
+ * Output, error and input streams used by the `io` builtins. By default
+ * the process stdout, stderr and stdin are used, embedders can redirect
+ * them (see `RuntimeBuilder` and `Runtime::set_stdout`).
+ * Streams are shared by all environments of a runtime, so redirecting
+ * them affects functions defined before as well.
+ */
+#[derive(Default)]
+pub struct Streams {
+    // None writes with `print!`/`eprint!`, so that test harnesses capture the output
+    out: Slot<dyn OutStream>,
+    err: Slot<dyn OutStream>,
+    // None reads the process stdin, which must not be wrapped in another
+    // buffer as it would steal input from other readers
+    input: Slot<dyn InStream>,
+}
+
+impl Streams {
+    pub fn set_out(&self, out: Box<dyn OutStream>) {
+        self.out.replace(Some(Shared::new(Lock::new(out))));
+    }
+
+    pub fn set_err(&self, err: Box<dyn OutStream>) {
+        self.err.replace(Some(Shared::new(Lock::new(err))));
+    }
+
+    pub fn set_input(&self, input: Box<dyn InStream>) {
+        self.input.replace(Some(Shared::new(Lock::new(input))));
+    }
+
+    /// Goes back to the process stdout, stderr and stdin
+    pub fn reset(&self) {
+        self.out.replace(None);
+        self.err.replace(None);
+        self.input.replace(None);
+    }
+
+    /// Streams writing to and reading from the same targets, which can be
+    /// redirected without affecting these
+    pub fn fork(&self) -> Self {
+        Self {
+            out: Lock::new(self.out.borrow().clone()),
+            err: Lock::new(self.err.borrow().clone()),
+            input: Lock::new(self.input.borrow().clone()),
+        }
+    }
+
+    pub fn write_out(&self, text: &str) -> io::Result<()> {
+        let out = self.out.borrow().clone();
+        match out {
+            Some(out) => {
+                let mut out = out.borrow_mut();
+                out.write_all(text.as_bytes())?;
+                out.flush()
+            }
+            None => {
+                print!("{}", text);
+                io::stdout().flush()
+            }
+        }
+    }
+
+    pub fn write_err(&self, text: &str) -> io::Result<()> {
+        let err = self.err.borrow().clone();
+        match err {
+            Some(err) => {
+                let mut err = err.borrow_mut();
+                err.write_all(text.as_bytes())?;
+                err.flush()
+            }
+            None => {
+                eprint!("{}", text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a line including the line terminator, returns 0 on end of input
+    pub fn read_line(&self, buf: &mut String) -> io::Result<usize> {
+        let input = self.input.borrow().clone();
+        match input {
+            Some(input) => input.borrow_mut().read_line(buf),
+            None => io::stdin().read_line(buf),
+        }
+    }
+
+    /// Reads everything up to the end of input
+    pub fn read_all(&self, buf: &mut String) -> io::Result<usize> {
+        let input = self.input.borrow().clone();
+        match input {
+            Some(input) => input.borrow_mut().read_to_string(buf),
+            None => io::stdin().read_to_string(buf),
+        }
+    }
+}
+
+/**
+ * In-memory stream that can be handed to `Streams` while a clone of it is
+ * kept to read what was written, e.g. to assert on the output of a script.
+ */
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Shared<Lock<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, invalid UTF-8 is replaced
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// Returns the contents and clears the buffer
+    pub fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.0.borrow_mut());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Shared ownership and interior mutability used by the interpreter.
+//! By default these are `Rc` and `RefCell`. The `sync` feature switches them
+//! to `Arc` and `Mutex` and requires natives, host objects and streams to be
+//! `Send + Sync`, which makes `Runtime` (and `Value`) `Send`.
+//! A runtime is still meant to be used by one thread at a time.
+
+#[cfg(not(feature = "sync"))]
+mod imp {
+    use std::cell::{Ref, RefCell, RefMut};
+
+    pub type Shared<T> = std::rc::Rc<T>;
+
+    pub type Weak<T> = std::rc::Weak<T>;
+
+    /// Data of host objects
+    pub type DynAny = dyn std::any::Any;
+
+    /// `Send` when the `sync` feature is enabled
+    pub trait MaybeSend {}
+
+    impl<T: ?Sized> MaybeSend for T {}
+
+    /// `Send + Sync` when the `sync` feature is enabled
+    pub trait MaybeSendSync {}
+
+    impl<T: ?Sized> MaybeSendSync for T {}
+
+    pub struct Lock<T: ?Sized>(RefCell<T>);
+
+    impl<T> Lock<T> {
+        pub fn new(value: T) -> Self {
+            Self(RefCell::new(value))
+        }
+    }
+
+    impl<T: ?Sized> Lock<T> {
+        pub fn borrow(&self) -> Ref<'_, T> {
+            self.0.borrow()
+        }
+
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+mod imp {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    pub type Shared<T> = std::sync::Arc<T>;
+
+    pub type Weak<T> = std::sync::Weak<T>;
+
+    /// Data of host objects
+    pub type DynAny = dyn std::any::Any + Send + Sync;
+
+    /// `Send` when the `sync` feature is enabled
+    pub trait MaybeSend: Send {}
+
+    impl<T: Send + ?Sized> MaybeSend for T {}
+
+    /// `Send + Sync` when the `sync` feature is enabled
+    pub trait MaybeSendSync: Send + Sync {}
+
+    impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+
+    /// A `Mutex` with the `RefCell` interface. Both borrows lock, so a value
+    /// must not be borrowed again while a borrow is alive, which `RefCell`
+    /// allows for shared borrows.
+    pub struct Lock<T: ?Sized>(Mutex<T>);
+
+    impl<T> Lock<T> {
+        pub fn new(value: T) -> Self {
+            Self(Mutex::new(value))
+        }
+    }
+
+    impl<T: ?Sized> Lock<T> {
+        pub fn borrow(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn borrow_mut(&self) -> MutexGuard<'_, T> {
+            self.borrow()
+        }
+    }
+}
+
+pub use imp::*;
+
+impl<T: Default> Default for Lock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Lock<T> {
+    pub fn replace(&self, value: T) -> T {
+        std::mem::replace(&mut *self.borrow_mut(), value)
+    }
+
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+}
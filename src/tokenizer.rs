@@ -4,7 +4,8 @@ pub fn tokenize(orig: &str) -> anyhow::Result<Vec<String>> {
     let mut buffer = String::new();
     let mut is_string = false;
     let mut escaped = false;
-    let mut is_comment = false;
+    // a shebang line (#!/usr/bin/env rssli) is skipped like a comment
+    let mut is_comment = orig.starts_with("#!");
 
     for ch in orig.chars() {
         if is_comment {
@@ -174,4 +175,18 @@ mod tests {
             vec!["(", "+", "1", "2", ")"]
         );
     }
+
+    #[test]
+    fn test_tokens_with_shebang() {
+        assert_eq!(
+            tokenize("#!/usr/bin/env rssli\n(+ 1 2)").unwrap(),
+            vec!["(", "+", "1", "2", ")"]
+        );
+        assert_eq!(tokenize("#!/usr/bin/env rssli").unwrap(), Vec::<String>::new());
+        // only on the first line
+        assert_eq!(
+            tokenize("(a)\n#!b\n").unwrap(),
+            vec!["(", "a", ")", "#!b"]
+        );
+    }
 }
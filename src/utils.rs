@@ -1,6 +1,8 @@
-use std::{cell::RefCell, collections::HashMap, hash::Hash, rc::Rc};
+use std::{collections::HashMap, hash::Hash};
 
-pub type HierCellMapRef<K, V> = Rc<RefCell<HierCellMap<K, V>>>;
+use crate::sync::{Lock, Shared};
+
+pub type HierCellMapRef<K, V> = Shared<Lock<HierCellMap<K, V>>>;
 
 #[derive(Clone)]
 pub struct HierCellMap<K, V>
@@ -109,7 +111,7 @@ where
     V: Clone,
 {
     pub fn new(parent: Option<HierCellMapRef<K, V>>) -> Self {
-        Self(Rc::new(RefCell::new(HierCellMap {
+        Self(Shared::new(Lock::new(HierCellMap {
             parent,
             data: Default::default(),
         })))
@@ -158,4 +160,13 @@ where
     pub fn curr_is_empty(&self) -> bool {
         self.0.borrow().data.is_empty()
     }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.0.borrow().parent.clone().map(Self)
+    }
+
+    /// Identity of the scope, equal for clones of the same wrapper
+    pub fn id(&self) -> usize {
+        Shared::as_ptr(&self.0) as *const () as usize
+    }
 }